@@ -0,0 +1,283 @@
+//! Field-level authorization auditing for use with [`async-graphql`](https://docs.rs/async-graphql)
+//!
+//! Register the [`Audit`] extension on a schema and every field protected by a guard created with
+//! [`guard`](crate::checks::guard) or [`guard_where`](crate::checks::guard_where) will emit an
+//! [`AuditRecord`] to the configured [`AuditSink`].
+
+use crate::{id::UserId, scope::Scope, user::User};
+use async_graphql::{
+    async_trait::async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    Context, Request, ServerResult,
+};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// A record of an authorization decision made for a field
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+pub struct AuditRecord {
+    /// The ID of the authenticated user, if any
+    pub user_id: Option<UserId>,
+    /// The ID of the user really acting, if the authenticated user is being impersonated
+    pub impersonator_id: Option<UserId>,
+    /// The scope of the request, if it was resolved
+    pub scope: Option<Scope>,
+    /// The path of the field being resolved
+    pub path: String,
+    /// Whether access to the field was allowed
+    pub allowed: bool,
+}
+
+/// A destination for audit records
+pub trait AuditSink: Send + Sync + 'static {
+    /// Persist the record
+    fn record(&self, record: AuditRecord);
+}
+
+impl<S> AuditSink for Arc<S>
+where
+    S: AuditSink,
+{
+    fn record(&self, record: AuditRecord) {
+        (**self).record(record)
+    }
+}
+
+/// An [`AuditSink`] that keeps all the records in memory
+///
+/// Mostly useful for testing.
+#[derive(Debug, Default)]
+pub struct InMemorySink(Mutex<Vec<AuditRecord>>);
+
+impl InMemorySink {
+    /// Get all the records received so far
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.0.lock().expect("lock must not be poisoned").clone()
+    }
+}
+
+impl AuditSink for InMemorySink {
+    fn record(&self, record: AuditRecord) {
+        self.0
+            .lock()
+            .expect("lock must not be poisoned")
+            .push(record);
+    }
+}
+
+/// An extension that records the outcome of guarded fields
+pub struct Audit {
+    sink: Arc<dyn AuditSink>,
+}
+
+impl Audit {
+    /// Create a new audit extension emitting records to the sink
+    pub fn new<S>(sink: S) -> Self
+    where
+        S: AuditSink,
+    {
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+}
+
+impl ExtensionFactory for Audit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AuditExtension {
+            sink: self.sink.clone(),
+        })
+    }
+}
+
+struct AuditExtension {
+    sink: Arc<dyn AuditSink>,
+}
+
+#[async_trait]
+impl Extension for AuditExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let request = request.data(Recorder(self.sink.clone()));
+        next.run(ctx, request).await
+    }
+}
+
+/// Per-request handle to the sink
+struct Recorder(Arc<dyn AuditSink>);
+
+/// Record the outcome of a guard if auditing is enabled
+pub(crate) fn record(ctx: &Context<'_>, allowed: bool) {
+    let Some(Recorder(sink)) = ctx.data_opt::<Recorder>() else {
+        return;
+    };
+
+    let (user_id, impersonator_id) = match ctx.data_opt::<User>() {
+        Some(User::Authenticated(user)) => (
+            Some(user.id),
+            user.impersonator
+                .as_ref()
+                .map(|impersonator| impersonator.id),
+        ),
+        _ => (None, None),
+    };
+    let scope = ctx.data_opt::<Scope>().cloned();
+    let path = ctx
+        .path_node
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+
+    sink.record(AuditRecord {
+        user_id,
+        impersonator_id,
+        scope,
+        path,
+        allowed,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Audit, AuditRecord, InMemorySink};
+    use crate::{
+        checks::{guard, guard_where, has_at_least_role, is_admin},
+        time::from_unix_seconds,
+        EventScope, Impersonator, OrganizationId, OrganizationScope, Scope, Slug, User, UserId,
+        UserRole,
+    };
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+    use std::sync::Arc;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        #[graphql(guard = "guard(is_admin)")]
+        async fn secret(&self) -> i32 {
+            1
+        }
+
+        #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+        async fn applications(&self) -> i32 {
+            2
+        }
+
+        async fn public(&self) -> i32 {
+            3
+        }
+    }
+
+    fn event() -> EventScope {
+        EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: None,
+        }
+    }
+
+    fn user(role: UserRole) -> User {
+        User::authenticated().id(42).role(role).build()
+    }
+
+    async fn execute(sink: Arc<InMemorySink>, query: &str, user: User) {
+        execute_in(sink, query, Scope::Event(event()), user).await
+    }
+
+    async fn execute_in(sink: Arc<InMemorySink>, query: &str, scope: Scope, user: User) {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(Audit::new(sink))
+            .finish();
+
+        let request = Request::new(query).data(scope).data(user);
+        schema.execute(request).await;
+    }
+
+    #[tokio::test]
+    async fn records_allowed_and_denied_fields() {
+        let sink = Arc::new(InMemorySink::default());
+        execute(
+            sink.clone(),
+            "{ secret applications }",
+            user(UserRole::Organizer),
+        )
+        .await;
+
+        let mut records = sink.records();
+        records.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            records,
+            vec![
+                AuditRecord {
+                    user_id: Some(UserId::from(42)),
+                    impersonator_id: None,
+                    scope: Some(Scope::Event(event())),
+                    path: String::from("applications"),
+                    allowed: true,
+                },
+                AuditRecord {
+                    user_id: Some(UserId::from(42)),
+                    impersonator_id: None,
+                    scope: Some(Scope::Event(event())),
+                    path: String::from("secret"),
+                    allowed: false,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn records_impersonator() {
+        let mut user = user(UserRole::Organizer);
+        if let User::Authenticated(context) = &mut user {
+            context.impersonator = Some(Impersonator {
+                id: UserId::from(7),
+                reason: String::from("debugging"),
+                started_at: from_unix_seconds(1_700_000_000),
+            });
+        }
+
+        let sink = Arc::new(InMemorySink::default());
+        execute(sink.clone(), "{ applications }", user).await;
+
+        let records = sink.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].user_id, Some(UserId::from(42)));
+        assert_eq!(records[0].impersonator_id, Some(UserId::from(7)));
+    }
+
+    #[tokio::test]
+    async fn records_non_event_scopes() {
+        let organization = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(5),
+        });
+
+        for scope in [Scope::Admin, organization] {
+            let sink = Arc::new(InMemorySink::default());
+            execute_in(
+                sink.clone(),
+                "{ secret }",
+                scope.clone(),
+                user(UserRole::Owner),
+            )
+            .await;
+
+            let records = sink.records();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].scope, Some(scope));
+        }
+    }
+
+    #[tokio::test]
+    async fn ignores_unguarded_fields() {
+        let sink = Arc::new(InMemorySink::default());
+        execute(sink.clone(), "{ public }", User::Unauthenticated).await;
+
+        assert!(sink.records().is_empty());
+    }
+}
@@ -0,0 +1,707 @@
+//! Pre-condition checks for use with [`axum`](https://docs.rs/axum) handlers
+//!
+//! These mirror the checks in [`checks`](crate::checks), but operate on the extracted [`Scope`]
+//! and [`User`] and reject with a [`Forbidden`] response. Checks that depend on the time have a
+//! `_with_clock` variant that reads it from the provided [`Clock`] instead of the system time.
+//!
+//! Each [`Forbidden`] and [`StepUpRequired`] rejection carries the [`Decision`] that led to it.
+//! Use [`Forbidden::explain_to`] or [`StepUpRequired::explain_to`] to include it in the response
+//! when the user is an admin.
+
+use crate::{
+    decision::Decision,
+    permission::{Permission, RolePermissions},
+    relationship::{RelationshipError, Relationships, Resource},
+    scope::{EventScope, OrganizationScope, Scope},
+    time::{Clock, SystemClock},
+    user::{AssuranceLevel, AuthenticatedUser, ParticipantStatus, ServiceUser, User, UserRole},
+};
+use axum_core::response::{IntoResponse, Response};
+use headers::HeaderMapExt;
+use http::{HeaderMap, StatusCode};
+use std::time::Duration;
+
+/// A rejection raised when the user has invalid permissions
+#[derive(Debug)]
+pub struct Forbidden {
+    decision: Decision,
+    explain: bool,
+}
+
+impl Forbidden {
+    /// Reject the request because of the decision
+    pub fn new(decision: Decision) -> Self {
+        Self {
+            decision,
+            explain: false,
+        }
+    }
+
+    /// The decision that led to the rejection
+    pub fn decision(&self) -> &Decision {
+        &self.decision
+    }
+
+    /// Include the decision in the response if the user is an admin
+    pub fn explain_to(mut self, user: &User) -> Self {
+        self.explain = matches!(user, User::Authenticated(user) if user.is_admin);
+        self
+    }
+}
+
+impl IntoResponse for Forbidden {
+    fn into_response(self) -> Response {
+        let explanation = self.explain.then_some(self.decision);
+        reject(StatusCode::FORBIDDEN, "forbidden", "FORBIDDEN", explanation)
+    }
+}
+
+/// A rejection raised when the user must re-authenticate before continuing
+#[derive(Debug)]
+pub struct StepUpRequired {
+    decision: Decision,
+    explain: bool,
+}
+
+impl StepUpRequired {
+    /// Reject the request because of the decision
+    pub fn new(decision: Decision) -> Self {
+        Self {
+            decision,
+            explain: false,
+        }
+    }
+
+    /// The decision that led to the rejection
+    pub fn decision(&self) -> &Decision {
+        &self.decision
+    }
+
+    /// Include the decision in the response if the user is an admin
+    pub fn explain_to(mut self, user: &User) -> Self {
+        self.explain = matches!(user, User::Authenticated(user) if user.is_admin);
+        self
+    }
+}
+
+impl IntoResponse for StepUpRequired {
+    fn into_response(self) -> Response {
+        let explanation = self.explain.then_some(self.decision);
+        reject(
+            StatusCode::UNAUTHORIZED,
+            "step-up authentication required",
+            "STEP_UP_REQUIRED",
+            explanation,
+        )
+    }
+}
+
+/// Build a JSON rejection, explaining the decision if one is provided
+fn reject(
+    status: StatusCode,
+    message: &str,
+    code: &str,
+    explanation: Option<Decision>,
+) -> Response {
+    let mut headers = HeaderMap::with_capacity(1);
+    headers.typed_insert(headers::ContentType::json());
+
+    let body = match explanation {
+        Some(decision) => serde_json::json!({
+            "message": message,
+            "code": code,
+            "explanation": decision,
+        }),
+        None => serde_json::json!({ "message": message, "code": code }),
+    };
+
+    (status, headers, body.to_string()).into_response()
+}
+
+/// A rejection raised by the step-up checks, i.e. [`requires_recent_auth`]
+#[derive(Debug)]
+pub enum StepUpRejection {
+    /// The user is not authenticated
+    Forbidden(Forbidden),
+    /// The user must re-authenticate before continuing
+    StepUpRequired(StepUpRequired),
+}
+
+impl StepUpRejection {
+    /// The decision that led to the rejection
+    pub fn decision(&self) -> &Decision {
+        match self {
+            Self::Forbidden(forbidden) => forbidden.decision(),
+            Self::StepUpRequired(step_up) => step_up.decision(),
+        }
+    }
+
+    /// Include the decision in the response if the user is an admin
+    pub fn explain_to(self, user: &User) -> Self {
+        match self {
+            Self::Forbidden(forbidden) => Self::Forbidden(forbidden.explain_to(user)),
+            Self::StepUpRequired(step_up) => Self::StepUpRequired(step_up.explain_to(user)),
+        }
+    }
+}
+
+impl From<Forbidden> for StepUpRejection {
+    fn from(forbidden: Forbidden) -> Self {
+        Self::Forbidden(forbidden)
+    }
+}
+
+impl From<StepUpRequired> for StepUpRejection {
+    fn from(step_up: StepUpRequired) -> Self {
+        Self::StepUpRequired(step_up)
+    }
+}
+
+impl IntoResponse for StepUpRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Forbidden(forbidden) => forbidden.into_response(),
+            Self::StepUpRequired(step_up) => step_up.into_response(),
+        }
+    }
+}
+
+/// A rejection raised by [`has_relationship`]
+#[derive(Debug)]
+pub enum RelationshipRejection {
+    /// The user does not have the relation with the resource
+    Forbidden(Forbidden),
+    /// The relationship could not be resolved
+    Failed(RelationshipError),
+}
+
+impl RelationshipRejection {
+    /// Include the decision in the response if the user is an admin
+    ///
+    /// Failed lookups never include an explanation since no decision was made.
+    pub fn explain_to(self, user: &User) -> Self {
+        match self {
+            Self::Forbidden(forbidden) => Self::Forbidden(forbidden.explain_to(user)),
+            Self::Failed(error) => Self::Failed(error),
+        }
+    }
+}
+
+impl From<Forbidden> for RelationshipRejection {
+    fn from(forbidden: Forbidden) -> Self {
+        Self::Forbidden(forbidden)
+    }
+}
+
+impl From<RelationshipError> for RelationshipRejection {
+    fn from(error: RelationshipError) -> Self {
+        Self::Failed(error)
+    }
+}
+
+impl IntoResponse for RelationshipRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Forbidden(forbidden) => forbidden.into_response(),
+            Self::Failed(error) => error.into_response(),
+        }
+    }
+}
+
+/// Evaluate the conditions of a check, rejecting with [`Forbidden`] if any fail
+fn decide<T>(check: impl FnOnce(&mut Decision) -> Option<T>) -> Result<T, Forbidden> {
+    let mut decision = Decision::new();
+    check(&mut decision).ok_or(Forbidden::new(decision))
+}
+
+/// Check if the requester is authenticated
+pub fn is_authenticated(user: &User) -> Result<&AuthenticatedUser, Forbidden> {
+    decide(|decision| decision.authenticated(user))
+}
+
+/// Ensure the user authenticated within the maximum age
+///
+/// Unauthenticated users are rejected as [`Forbidden`] since stepping up cannot help them.
+pub fn requires_recent_auth(
+    user: &User,
+    max_age: Duration,
+) -> Result<&AuthenticatedUser, StepUpRejection> {
+    requires_recent_auth_with_clock(user, max_age, &SystemClock)
+}
+
+/// Ensure the user authenticated within the maximum age, according to the clock
+pub fn requires_recent_auth_with_clock<'u>(
+    user: &'u User,
+    max_age: Duration,
+    clock: &dyn Clock,
+) -> Result<&'u AuthenticatedUser, StepUpRejection> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(user) else {
+        return Err(Forbidden::new(decision).into());
+    };
+
+    let recent = user.authenticated_within(max_age, clock.now());
+    match decision.require(
+        format!("authenticated within {}s", max_age.as_secs()),
+        recent,
+    ) {
+        Some(()) => Ok(user),
+        None => Err(StepUpRequired::new(decision).into()),
+    }
+}
+
+/// Ensure the user authenticated with at least the required assurance level
+///
+/// Unauthenticated users are rejected as [`Forbidden`] since stepping up cannot help them.
+pub fn requires_assurance(
+    user: &User,
+    level: AssuranceLevel,
+) -> Result<&AuthenticatedUser, StepUpRejection> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(user) else {
+        return Err(Forbidden::new(decision).into());
+    };
+
+    match decision.require(
+        format!("assurance at least {}", level.as_str()),
+        user.has_assurance(level),
+    ) {
+        Some(()) => Ok(user),
+        None => Err(StepUpRequired::new(decision).into()),
+    }
+}
+
+/// Ensure the authenticated user is acting on their own behalf
+///
+/// Use this to protect sensitive actions from being performed while impersonating.
+pub fn not_impersonated(user: &User) -> Result<&AuthenticatedUser, Forbidden> {
+    decide(|decision| {
+        let user = decision.authenticated(user)?;
+        decision.require("not impersonated", !user.is_impersonated())?;
+        Some(user)
+    })
+}
+
+/// Check if the request is being made by a service
+pub fn is_service(user: &User) -> Result<&ServiceUser, Forbidden> {
+    decide(|decision| decision.service(user))
+}
+
+/// Check if the request is being made by the named service
+pub fn is_service_named<'u>(user: &'u User, name: &str) -> Result<&'u ServiceUser, Forbidden> {
+    decide(|decision| {
+        let service = decision.service(user)?;
+        decision.require(format!("service named {name}"), service.name == name)?;
+        Some(service)
+    })
+}
+
+/// Ensure the request is being made by a service that was granted the permission
+pub fn service_has_permission(
+    user: &User,
+    permission: Permission,
+) -> Result<&ServiceUser, Forbidden> {
+    decide(|decision| {
+        let service = decision.service(user)?;
+        decision.require(
+            format!("service granted {}", permission.as_str()),
+            service.has_permission(permission),
+        )?;
+        Some(service)
+    })
+}
+
+/// Check if the request was scoped to an event
+pub fn is_event(scope: &Scope) -> Result<&EventScope, Forbidden> {
+    decide(|decision| decision.event(scope))
+}
+
+/// Check if the request was scoped to an organization
+pub fn is_organization(scope: &Scope) -> Result<&OrganizationScope, Forbidden> {
+    decide(|decision| decision.organization(scope))
+}
+
+/// Ensure the user's role for the event or organization grants the permission
+///
+/// Roles outside of their window are treated as no role.
+pub fn has_permission(
+    scope: &Scope,
+    user: &User,
+    permissions: &RolePermissions,
+    permission: Permission,
+) -> Result<UserRole, Forbidden> {
+    has_permission_with_clock(scope, user, permissions, permission, &SystemClock)
+}
+
+/// Ensure the user's role for the event or organization grants the permission, checking the
+/// role's window against the clock
+pub fn has_permission_with_clock(
+    scope: &Scope,
+    user: &User,
+    permissions: &RolePermissions,
+    permission: Permission,
+    clock: &dyn Clock,
+) -> Result<UserRole, Forbidden> {
+    decide(|decision| {
+        let role = decision
+            .scoped_role(scope, user, clock.now())?
+            .filter(|role| permissions.allows(role, permission));
+        decision.require(
+            format!("role grants {}", permission.as_str()),
+            role.is_some(),
+        )?;
+        role
+    })
+}
+
+/// Ensure the user is a participant of the event with one of the statuses
+pub fn has_participant_status(
+    scope: &Scope,
+    user: &User,
+    statuses: &[ParticipantStatus],
+) -> Result<ParticipantStatus, Forbidden> {
+    decide(|decision| decision.participant_status(scope, user, statuses))
+}
+
+/// Ensure the user has the relation, i.e. `owner` or `member`, with the resource
+///
+/// Requests fail with an internal error if the lookup fails.
+pub async fn has_relationship<'u>(
+    user: &'u User,
+    relationships: &Relationships,
+    relation: &str,
+    resource: &Resource,
+) -> Result<&'u AuthenticatedUser, RelationshipRejection> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(user) else {
+        return Err(Forbidden::new(decision).into());
+    };
+
+    let related = relationships
+        .has_relation(user.id, relation, resource)
+        .await?;
+    match decision.require(format!("{relation} of {resource}"), related) {
+        Some(()) => Ok(user),
+        None => Err(Forbidden::new(decision).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        has_participant_status, has_permission, has_permission_with_clock, is_service_named,
+        requires_assurance, requires_recent_auth, requires_recent_auth_with_clock,
+        service_has_permission, StepUpRejection,
+    };
+    use crate::{
+        time::from_unix_seconds, Assurance, AssuranceLevel, EventScope, FixedClock, OrganizationId,
+        OrganizationScope, ParticipantStatus, Permission, RolePermissions, RoleWindow, Scope,
+        ServiceUser, Slug, User, UserRole,
+    };
+    use axum_core::response::IntoResponse;
+    use http::StatusCode;
+    use std::time::{Duration, SystemTime};
+
+    fn scope() -> Scope {
+        Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: None,
+        })
+    }
+
+    fn user(role: Option<UserRole>) -> User {
+        let user = User::authenticated().id(42);
+        match role {
+            Some(role) => user.role(role).build(),
+            None => user.build(),
+        }
+    }
+
+    #[test]
+    fn has_permission_with_granted_role() {
+        let role = has_permission(
+            &scope(),
+            &user(Some(UserRole::Organizer)),
+            &RolePermissions::default(),
+            Permission::CheckIn,
+        )
+        .unwrap();
+        assert_eq!(role, UserRole::Organizer);
+    }
+
+    #[test]
+    fn has_permission_with_custom_mapping() {
+        let permissions =
+            RolePermissions::default().revoke(UserRole::Organizer, Permission::ReadApplications);
+        let user = user(Some(UserRole::Organizer));
+
+        assert!(has_permission(&scope(), &user, &permissions, Permission::CheckIn).is_ok());
+        assert!(
+            has_permission(&scope(), &user, &permissions, Permission::ReadApplications).is_err()
+        );
+    }
+
+    #[test]
+    fn has_permission_requires_role() {
+        let result = has_permission(
+            &scope(),
+            &user(None),
+            &RolePermissions::default(),
+            Permission::SubmitApplication,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn has_permission_in_organization_scope() {
+        let scope = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(5),
+        });
+        let permissions = RolePermissions::default();
+
+        let role = has_permission(
+            &scope,
+            &user(Some(UserRole::Manager)),
+            &permissions,
+            Permission::ManageOrganization,
+        )
+        .unwrap();
+        assert_eq!(role, UserRole::Manager);
+
+        let result = has_permission(
+            &scope,
+            &user(Some(UserRole::Participant)),
+            &permissions,
+            Permission::SubmitApplication,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn has_permission_requires_event_or_organization_scope() {
+        let result = has_permission(
+            &Scope::User,
+            &user(Some(UserRole::Director)),
+            &RolePermissions::default(),
+            Permission::CheckIn,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn service_checks() {
+        let service = User::Service(ServiceUser {
+            name: String::from("mailer"),
+            permissions: vec![Permission::ReadApplications],
+        });
+
+        assert!(is_service_named(&service, "mailer").is_ok());
+        assert!(is_service_named(&service, "payments").is_err());
+        assert!(service_has_permission(&service, Permission::ReadApplications).is_ok());
+        assert!(service_has_permission(&service, Permission::CheckIn).is_err());
+        assert!(
+            service_has_permission(&user(Some(UserRole::Director)), Permission::CheckIn).is_err()
+        );
+    }
+
+    #[test]
+    fn step_up_checks() {
+        let User::Authenticated(mut context) = user(None) else {
+            unreachable!()
+        };
+        let user = User::Authenticated(context.clone());
+        assert!(matches!(
+            requires_recent_auth(&user, Duration::from_secs(300)),
+            Err(StepUpRejection::StepUpRequired(_))
+        ));
+        assert!(matches!(
+            requires_assurance(&user, AssuranceLevel::Password),
+            Err(StepUpRejection::StepUpRequired(_))
+        ));
+
+        context.assurance = Some(Assurance {
+            level: AssuranceLevel::OAuth,
+            authenticated_at: SystemTime::now() - Duration::from_secs(60),
+        });
+        let user = User::Authenticated(context);
+        assert!(requires_recent_auth(&user, Duration::from_secs(300)).is_ok());
+        assert!(requires_recent_auth(&user, Duration::from_secs(30)).is_err());
+        assert!(requires_assurance(&user, AssuranceLevel::OAuth).is_ok());
+        assert_eq!(
+            requires_assurance(&user, AssuranceLevel::Mfa)
+                .unwrap_err()
+                .decision()
+                .failed(),
+            ["assurance at least mfa"]
+        );
+    }
+
+    #[test]
+    fn step_up_requires_authentication() {
+        assert!(matches!(
+            requires_recent_auth(&User::Unauthenticated, Duration::from_secs(300)),
+            Err(StepUpRejection::Forbidden(_))
+        ));
+        assert!(matches!(
+            requires_assurance(&User::Unauthenticated, AssuranceLevel::Password),
+            Err(StepUpRejection::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn recent_auth_with_clock() {
+        let User::Authenticated(mut context) = user(None) else {
+            unreachable!()
+        };
+        context.assurance = Some(Assurance {
+            level: AssuranceLevel::OAuth,
+            authenticated_at: from_unix_seconds(1_700_000_000),
+        });
+        let user = User::Authenticated(context);
+
+        let clock = FixedClock::new(from_unix_seconds(1_700_000_060));
+        assert!(requires_recent_auth_with_clock(&user, Duration::from_secs(300), &clock).is_ok());
+        assert!(requires_recent_auth_with_clock(&user, Duration::from_secs(30), &clock).is_err());
+    }
+
+    #[test]
+    fn has_permission_respects_role_window() {
+        let User::Authenticated(mut context) = user(Some(UserRole::Organizer)) else {
+            unreachable!()
+        };
+        context.role_window = Some(RoleWindow {
+            valid_from: from_unix_seconds(1_700_000_000),
+            valid_until: from_unix_seconds(1_700_172_800),
+        });
+        let user = User::Authenticated(context);
+        let permissions = RolePermissions::default();
+        let check = |seconds| {
+            let clock = FixedClock::new(from_unix_seconds(seconds));
+            has_permission_with_clock(&scope(), &user, &permissions, Permission::CheckIn, &clock)
+        };
+
+        assert!(check(1_699_999_999).is_err());
+        assert_eq!(check(1_700_000_000).unwrap(), UserRole::Organizer);
+        assert_eq!(check(1_700_100_000).unwrap(), UserRole::Organizer);
+        assert!(check(1_700_172_800).is_err());
+        assert!(has_permission(&scope(), &user, &permissions, Permission::CheckIn).is_err());
+    }
+
+    #[test]
+    fn participant_status_checks() {
+        let User::Authenticated(mut context) = user(Some(UserRole::Participant)) else {
+            unreachable!()
+        };
+        let allowed = [ParticipantStatus::Accepted, ParticipantStatus::CheckedIn];
+        let user = User::Authenticated(context.clone());
+        assert!(has_participant_status(&scope(), &user, &allowed).is_err());
+
+        context.participant_status = Some(ParticipantStatus::Waitlisted);
+        let user = User::Authenticated(context.clone());
+        assert_eq!(
+            has_participant_status(&scope(), &user, &allowed)
+                .unwrap_err()
+                .decision()
+                .failed(),
+            ["participant status is one of accepted, checked-in"]
+        );
+
+        context.participant_status = Some(ParticipantStatus::CheckedIn);
+        let user = User::Authenticated(context);
+        assert_eq!(
+            has_participant_status(&scope(), &user, &allowed).unwrap(),
+            ParticipantStatus::CheckedIn
+        );
+        assert!(has_participant_status(&Scope::User, &user, &allowed).is_err());
+    }
+
+    #[test]
+    fn rejection_carries_decision() {
+        let rejection = has_permission(
+            &scope(),
+            &user(Some(UserRole::Volunteer)),
+            &RolePermissions::default(),
+            Permission::ReadApplications,
+        )
+        .unwrap_err();
+
+        let decision = rejection.decision();
+        assert!(!decision.is_allowed());
+        assert_eq!(
+            decision.evaluated(),
+            [
+                "event or organization scope",
+                "authenticated",
+                "role grants read-applications"
+            ]
+        );
+        assert_eq!(decision.failed(), ["role grants read-applications"]);
+    }
+
+    #[tokio::test]
+    async fn step_up_carries_decision() {
+        let User::Authenticated(mut admin) = user(None) else {
+            unreachable!()
+        };
+        admin.is_admin = true;
+        let admin = User::Authenticated(admin);
+
+        let rejection = requires_recent_auth(&admin, Duration::from_secs(300)).unwrap_err();
+        assert_eq!(rejection.decision().failed(), ["authenticated within 300s"]);
+
+        let response = rejection.explain_to(&admin).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({
+                "message": "step-up authentication required",
+                "code": "STEP_UP_REQUIRED",
+                "explanation": {
+                    "allowed": false,
+                    "evaluated": ["authenticated", "authenticated within 300s"],
+                    "failed": ["authenticated within 300s"],
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn explains_rejection_to_admins() {
+        let body = |user: User| async move {
+            let rejection = is_service_named(&user, "mailer").unwrap_err();
+            let response = rejection.explain_to(&user).into_response();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let User::Authenticated(mut admin) = user(None) else {
+            unreachable!()
+        };
+        admin.is_admin = true;
+        assert_eq!(
+            body(User::Authenticated(admin)).await,
+            serde_json::json!({
+                "message": "forbidden",
+                "code": "FORBIDDEN",
+                "explanation": {
+                    "allowed": false,
+                    "evaluated": ["service"],
+                    "failed": ["service"],
+                },
+            })
+        );
+
+        assert_eq!(
+            body(user(None)).await,
+            serde_json::json!({ "message": "forbidden", "code": "FORBIDDEN" })
+        );
+    }
+}
@@ -1,17 +1,40 @@
 //! Pre-condition checks for use with [`async-graphql`](https://docs.rs/async-graphql)
+//!
+//! Checks that depend on the time read it from an `Arc<dyn Clock>` in the context data, falling
+//! back to the [`SystemClock`](crate::SystemClock) if none was provided.
+//!
+//! Denied requests carry the [`Decision`] that led to them as the error's
+//! [`source`](async_graphql::Error::source). Add [`Explanations::Admins`] to the context data to
+//! also include it in the `explanation` extension of errors shown to admins.
 
 use crate::{
-    scope::{self, EventScope},
-    user::{self, AuthenticatedUser, UserRole},
+    audit,
+    decision::{Decision, Explanations},
+    permission::{Permission, RolePermissions},
+    relationship::{RelationshipError, Relationships, Resource},
+    scope::{EventScope, OrganizationScope, Scope},
+    time::Clock,
+    user::{
+        AssuranceLevel, AuthenticatedUser, Impersonator, ParticipantStatus, ServiceUser, User,
+        UserRole,
+    },
 };
 use async_graphql::{Context, Error, ErrorExtensions, Result};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 /// Create an [`async_graphql::Guard`] out of a check function
 pub fn guard<F, R>(check: F) -> impl Fn(&Context<'_>) -> Result<()> + Send + Sync + 'static
 where
     F: Fn(&Context<'_>) -> Result<R> + Send + Sync + 'static,
 {
-    move |ctx| check(ctx).map(|_| ())
+    move |ctx| {
+        let result = check(ctx).map(|_| ());
+        audit::record(ctx, result.is_ok());
+        result
+    }
 }
 
 /// Create a [`async_graphql::Guard`] out of a check function that requires an argument
@@ -20,10 +43,14 @@ pub fn guard_where<F, A, R>(
     argument: A,
 ) -> impl Fn(&Context<'_>) -> Result<()> + Send + Sync + 'static
 where
-    A: Copy + Send + Sync + 'static,
+    A: Clone + Send + Sync + 'static,
     F: Fn(&Context<'_>, A) -> Result<R> + Send + Sync + 'static,
 {
-    move |ctx| check(ctx, argument).map(|_| ())
+    move |ctx| {
+        let result = check(ctx, argument.clone()).map(|_| ());
+        audit::record(ctx, result.is_ok());
+        result
+    }
 }
 
 /// An error raised when the user has invalid permissions
@@ -36,80 +63,550 @@ impl From<Forbidden> for Error {
     }
 }
 
+/// An error raised when the user must re-authenticate before continuing
+#[derive(Debug)]
+pub struct StepUpRequired;
+
+impl From<StepUpRequired> for Error {
+    fn from(_: StepUpRequired) -> Self {
+        Error::new("step-up authentication required")
+            .extend_with(|_, extensions| extensions.set("code", "STEP_UP_REQUIRED"))
+    }
+}
+
+/// Get the current time from the clock in the context data
+pub(crate) fn now(ctx: &Context<'_>) -> SystemTime {
+    match ctx.data_opt::<Arc<dyn Clock>>() {
+        Some(clock) => clock.now(),
+        None => SystemTime::now(),
+    }
+}
+
+/// Evaluate the conditions of a check, rejecting with [`Forbidden`] if any fail
+pub(crate) fn decide<T>(
+    ctx: &Context<'_>,
+    check: impl FnOnce(&mut Decision) -> Option<T>,
+) -> Result<T> {
+    let mut decision = Decision::new();
+    match check(&mut decision) {
+        Some(value) => Ok(value),
+        None => Err(deny(ctx, Forbidden.into(), decision)),
+    }
+}
+
+/// Attach the decision to the error, explaining it if enabled and the user is an admin
+fn deny(ctx: &Context<'_>, error: Error, decision: Decision) -> Error {
+    let explain = ctx.data_opt::<Explanations>() == Some(&Explanations::Admins)
+        && matches!(ctx.data_opt::<User>(), Some(User::Authenticated(user)) if user.is_admin);
+
+    let mut error = match async_graphql::to_value(&decision) {
+        Ok(explanation) if explain => {
+            error.extend_with(|_, extensions| extensions.set("explanation", explanation))
+        }
+        _ => error,
+    };
+    error.source = Some(Arc::new(decision));
+    error
+}
+
+/// Fail with an internal error without revealing why the relationship couldn't be resolved
+fn lookup_failed(error: RelationshipError) -> Error {
+    let mut failed = Error::new("failed to resolve relationship")
+        .extend_with(|_, extensions| extensions.set("code", "INTERNAL_SERVER_ERROR"));
+    failed.source = Some(Arc::new(error));
+    failed
+}
+
 /// Check if the requester is authenticated
 pub fn is_authenticated<'c>(ctx: &'c Context) -> Result<&'c AuthenticatedUser> {
-    let user = ctx.data_unchecked::<user::User>();
+    decide(ctx, |decision| {
+        decision.authenticated(ctx.data_unchecked::<User>())
+    })
+}
 
-    match user {
-        user::User::Authenticated(context) => Ok(context),
-        _ => Err(Forbidden.into()),
+/// Ensure the user authenticated within the maximum age
+pub fn requires_recent_auth<'c>(
+    ctx: &'c Context,
+    max_age: Duration,
+) -> Result<&'c AuthenticatedUser> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(ctx.data_unchecked::<User>()) else {
+        return Err(deny(ctx, Forbidden.into(), decision));
+    };
+
+    let recent = user.authenticated_within(max_age, now(ctx));
+    match decision.require(
+        format!("authenticated within {}s", max_age.as_secs()),
+        recent,
+    ) {
+        Some(()) => Ok(user),
+        None => Err(deny(ctx, StepUpRequired.into(), decision)),
     }
 }
 
-/// Check if the request was scoped to an user
-pub fn is_user(ctx: &Context<'_>) -> Result<()> {
-    let scope = ctx.data_unchecked::<scope::Scope>();
+/// Ensure the user authenticated with at least the required assurance level
+pub fn requires_assurance<'c>(
+    ctx: &'c Context,
+    level: AssuranceLevel,
+) -> Result<&'c AuthenticatedUser> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(ctx.data_unchecked::<User>()) else {
+        return Err(deny(ctx, Forbidden.into(), decision));
+    };
 
-    match scope {
-        scope::Scope::User => Ok(()),
-        _ => Err(Forbidden.into()),
+    match decision.require(
+        format!("assurance at least {}", level.as_str()),
+        user.has_assurance(level),
+    ) {
+        Some(()) => Ok(user),
+        None => Err(deny(ctx, StepUpRequired.into(), decision)),
     }
 }
 
+/// Check if the authenticated user is being impersonated
+pub fn is_impersonated<'c>(ctx: &'c Context) -> Result<&'c Impersonator> {
+    decide(ctx, |decision| {
+        let user = decision.authenticated(ctx.data_unchecked::<User>())?;
+        decision.require("impersonated", user.is_impersonated())?;
+        user.impersonator.as_ref()
+    })
+}
+
+/// Ensure the authenticated user is acting on their own behalf
+///
+/// Use this to protect sensitive mutations from being performed while impersonating.
+pub fn not_impersonated<'c>(ctx: &'c Context) -> Result<&'c AuthenticatedUser> {
+    decide(ctx, |decision| {
+        let user = decision.authenticated(ctx.data_unchecked::<User>())?;
+        decision.require("not impersonated", !user.is_impersonated())?;
+        Some(user)
+    })
+}
+
+/// Check if the request is being made by a service
+pub fn is_service<'c>(ctx: &'c Context) -> Result<&'c ServiceUser> {
+    decide(ctx, |decision| {
+        decision.service(ctx.data_unchecked::<User>())
+    })
+}
+
+/// Check if the request is being made by the named service
+pub fn is_service_named<'c>(ctx: &'c Context, name: &'static str) -> Result<&'c ServiceUser> {
+    decide(ctx, |decision| {
+        let service = decision.service(ctx.data_unchecked::<User>())?;
+        decision.require(format!("service named {name}"), service.name == name)?;
+        Some(service)
+    })
+}
+
+/// Ensure the request is being made by a service that was granted the permission
+pub fn service_has_permission<'c>(
+    ctx: &'c Context,
+    permission: Permission,
+) -> Result<&'c ServiceUser> {
+    decide(ctx, |decision| {
+        let service = decision.service(ctx.data_unchecked::<User>())?;
+        decision.require(
+            format!("service granted {}", permission.as_str()),
+            service.has_permission(permission),
+        )?;
+        Some(service)
+    })
+}
+
+/// Check if the request was scoped to an user
+pub fn is_user(ctx: &Context<'_>) -> Result<()> {
+    decide(ctx, |decision| {
+        let scope = ctx.data_unchecked::<Scope>();
+        decision.require("user scope", matches!(scope, Scope::User))
+    })
+}
+
 /// Check if the request was scoped to an event
 pub fn is_event<'c>(ctx: &Context<'c>) -> Result<&'c EventScope> {
-    let scope = ctx.data_unchecked::<scope::Scope>();
+    decide(ctx, |decision| {
+        decision.event(ctx.data_unchecked::<Scope>())
+    })
+}
 
-    match scope {
-        scope::Scope::Event(context) => Ok(context),
-        _ => Err(Forbidden.into()),
-    }
+/// Check if the request was scoped to an organization
+pub fn is_organization<'c>(ctx: &Context<'c>) -> Result<&'c OrganizationScope> {
+    decide(ctx, |decision| {
+        decision.organization(ctx.data_unchecked::<Scope>())
+    })
+}
+
+/// Get the user's role for the event the request is scoped to
+///
+/// Roles outside of their window are treated as no role.
+fn event_role(ctx: &Context<'_>, decision: &mut Decision) -> Option<Option<UserRole>> {
+    decision.event_role(
+        ctx.data_unchecked::<Scope>(),
+        ctx.data_unchecked::<User>(),
+        now(ctx),
+    )
+}
+
+/// Get the user's role for the organization the request is scoped to
+///
+/// Roles outside of their window are treated as no role.
+fn organization_role(ctx: &Context<'_>, decision: &mut Decision) -> Option<Option<UserRole>> {
+    decision.organization_role(
+        ctx.data_unchecked::<Scope>(),
+        ctx.data_unchecked::<User>(),
+        now(ctx),
+    )
+}
+
+/// Get the user's role for the event or organization the request is scoped to
+///
+/// Roles outside of their window are treated as no role.
+fn scoped_role(ctx: &Context<'_>, decision: &mut Decision) -> Option<Option<UserRole>> {
+    decision.scoped_role(
+        ctx.data_unchecked::<Scope>(),
+        ctx.data_unchecked::<User>(),
+        now(ctx),
+    )
 }
 
 /// Check if the requester is an administrator
 pub fn is_admin(ctx: &Context<'_>) -> Result<()> {
-    let user = is_authenticated(ctx)?;
-
-    if user.is_admin {
-        Ok(())
-    } else {
-        Err(Forbidden.into())
-    }
+    decide(ctx, |decision| {
+        let user = decision.authenticated(ctx.data_unchecked::<User>())?;
+        decision.require("admin", user.is_admin)
+    })
 }
 
 /// Ensures only admins can access a resource
 pub fn admin_only(ctx: &Context<'_>) -> Result<()> {
-    is_admin(ctx)?;
+    decide(ctx, |decision| {
+        let user = decision.authenticated(ctx.data_unchecked::<User>())?;
+        decision.require("admin", user.is_admin)?;
 
-    let scope = ctx.data_unchecked::<scope::Scope>();
-    match scope {
-        scope::Scope::Admin => Ok(()),
-        _ => Err(Forbidden.into()),
-    }
+        let scope = ctx.data_unchecked::<Scope>();
+        decision.require("admin scope", matches!(scope, Scope::Admin))
+    })
 }
 
 /// Ensure the user has the required role for the event
+///
+/// Use [`has_organization_role`] for requests scoped to an organization.
 pub fn has_role(ctx: &Context<'_>, role: UserRole) -> Result<()> {
-    is_event(ctx)?;
-    let user = is_authenticated(ctx)?;
-
-    if user.role == Some(role) {
-        Ok(())
-    } else {
-        Err(Forbidden.into())
-    }
+    decide(ctx, |decision| {
+        let condition = format!("role is {}", role.as_str());
+        let user_role = event_role(ctx, decision)?;
+        decision.require(condition, user_role == Some(role))
+    })
 }
 
 /// Ensure the user has at least the required role for the event
+///
+/// Unknown roles never satisfy the check, see [`UserRole::is_at_least`]. Use
+/// [`has_at_least_organization_role`] for requests scoped to an organization.
 pub fn has_at_least_role(ctx: &Context<'_>, role: UserRole) -> Result<UserRole> {
-    is_event(ctx)?;
-    let user = is_authenticated(ctx)?;
+    decide(ctx, |decision| {
+        let user_role = event_role(ctx, decision)?.filter(|user_role| user_role.is_at_least(&role));
+        decision.require(
+            format!("role at least {}", role.as_str()),
+            user_role.is_some(),
+        )?;
+        user_role
+    })
+}
+
+/// Ensure the user has the required role for the organization
+///
+/// Participants and volunteers never have a role for an organization, see
+/// [`AuthenticatedUser::role_for`].
+pub fn has_organization_role(ctx: &Context<'_>, role: UserRole) -> Result<()> {
+    decide(ctx, |decision| {
+        let condition = format!("role is {}", role.as_str());
+        let user_role = organization_role(ctx, decision)?;
+        decision.require(condition, user_role == Some(role))
+    })
+}
+
+/// Ensure the user has at least the required role for the organization
+///
+/// Unknown roles never satisfy the check, see [`UserRole::is_at_least`].
+pub fn has_at_least_organization_role(ctx: &Context<'_>, role: UserRole) -> Result<UserRole> {
+    decide(ctx, |decision| {
+        let user_role =
+            organization_role(ctx, decision)?.filter(|user_role| user_role.is_at_least(&role));
+        decision.require(
+            format!("role at least {}", role.as_str()),
+            user_role.is_some(),
+        )?;
+        user_role
+    })
+}
+
+/// Ensure the user's role for the event or organization grants the permission
+///
+/// The role mapping is taken from the [`RolePermissions`] in the context data, falling back to
+/// the default mapping if none was provided.
+pub fn has_permission(ctx: &Context<'_>, permission: Permission) -> Result<UserRole> {
+    let permissions = ctx
+        .data_opt::<RolePermissions>()
+        .unwrap_or_else(|| RolePermissions::default_ref());
+
+    decide(ctx, |decision| {
+        let role = scoped_role(ctx, decision)?.filter(|role| permissions.allows(role, permission));
+        decision.require(
+            format!("role grants {}", permission.as_str()),
+            role.is_some(),
+        )?;
+        role
+    })
+}
 
-    if let Some(user_role) = user.role {
-        if user_role >= role {
-            return Ok(user_role);
+/// Ensure the user is a participant of the event with one of the statuses
+pub fn has_participant_status(
+    ctx: &Context<'_>,
+    statuses: &'static [ParticipantStatus],
+) -> Result<ParticipantStatus> {
+    decide(ctx, |decision| {
+        decision.participant_status(
+            ctx.data_unchecked::<Scope>(),
+            ctx.data_unchecked::<User>(),
+            statuses,
+        )
+    })
+}
+
+/// Ensure the user has the relation, i.e. `owner` or `member`, with the resource
+///
+/// Relationships are looked up through the [`Relationships`] in the context data. Requests are
+/// denied if none were provided, and fail with an internal error if the lookup fails. Use a
+/// [`RelationshipGuard`] to check it before the field is resolved, or call this from the resolver:
+///
+/// ```ignore
+/// has_relationship(ctx, "owner", &Resource::new("application", id)).await?;
+/// ```
+pub async fn has_relationship<'c>(
+    ctx: &'c Context<'_>,
+    relation: &str,
+    resource: &Resource,
+) -> Result<&'c AuthenticatedUser> {
+    let result = check_relationship(ctx, relation, resource).await;
+    audit::record(ctx, result.is_ok());
+    result
+}
+
+/// Check the relationship without recording the outcome
+async fn check_relationship<'c>(
+    ctx: &'c Context<'_>,
+    relation: &str,
+    resource: &Resource,
+) -> Result<&'c AuthenticatedUser> {
+    let mut decision = Decision::new();
+    let Some(user) = decision.authenticated(ctx.data_unchecked::<User>()) else {
+        return Err(deny(ctx, Forbidden.into(), decision));
+    };
+
+    let related = match ctx.data_opt::<Relationships>() {
+        Some(relationships) => relationships
+            .has_relation(user.id, relation, resource)
+            .await
+            .map_err(lookup_failed)?,
+        None => false,
+    };
+
+    match decision.require(format!("{relation} of {resource}"), related) {
+        Some(()) => Ok(user),
+        None => Err(deny(ctx, Forbidden.into(), decision)),
+    }
+}
+
+/// An [`async_graphql::Guard`] ensuring the user has the relation with the resource
+///
+/// Guards can refer to the field's arguments, so the resource is usually built from one:
+///
+/// ```ignore
+/// #[graphql(guard = "RelationshipGuard::new(\"owner\", Resource::new(\"application\", id))")]
+/// async fn application(&self, id: i32) -> Application {
+///     // ...
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RelationshipGuard {
+    relation: String,
+    resource: Resource,
+}
+
+impl RelationshipGuard {
+    /// Require the relation, i.e. `owner` or `member`, with the resource
+    pub fn new(relation: impl Into<String>, resource: Resource) -> Self {
+        Self {
+            relation: relation.into(),
+            resource,
+        }
+    }
+}
+
+impl async_graphql::Guard for RelationshipGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        has_relationship(ctx, &self.relation, &self.resource)
+            .await
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        guard_where, has_at_least_organization_role, has_at_least_role, has_organization_role,
+        has_permission, has_role,
+    };
+    use crate::{
+        time::from_unix_seconds, Clock, EventScope, FixedClock, OrganizationId, OrganizationScope,
+        Permission, RolePermissions, RoleWindow, Scope, Slug, User, UserRole,
+    };
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+    use std::sync::Arc;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        #[graphql(guard = "guard_where(has_role, UserRole::Director)")]
+        async fn event_settings(&self) -> i32 {
+            1
+        }
+
+        #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+        async fn applications(&self) -> i32 {
+            2
+        }
+
+        #[graphql(guard = "guard_where(has_organization_role, UserRole::Director)")]
+        async fn members(&self) -> i32 {
+            3
+        }
+
+        #[graphql(guard = "guard_where(has_at_least_organization_role, UserRole::Organizer)")]
+        async fn reports(&self) -> i32 {
+            4
+        }
+
+        #[graphql(guard = "guard_where(has_permission, Permission::CheckIn)")]
+        async fn check_in(&self) -> i32 {
+            5
         }
     }
 
-    Err(Forbidden.into())
+    fn event() -> Scope {
+        Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: None,
+        })
+    }
+
+    fn organization() -> Scope {
+        Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(5),
+        })
+    }
+
+    fn user(role: UserRole) -> User {
+        User::authenticated().id(42).role(role).build()
+    }
+
+    /// Whether the user can access the field when the request has the scope
+    async fn allowed(field: &str, scope: Scope, user: User) -> bool {
+        let request = Request::new(format!("{{ {field} }}"))
+            .data(scope)
+            .data(user);
+        succeeds(request).await
+    }
+
+    async fn succeeds(request: Request) -> bool {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription).finish();
+        schema.execute(request).await.errors.is_empty()
+    }
+
+    #[tokio::test]
+    async fn event_roles_only_apply_to_events() {
+        let director = || user(UserRole::Director);
+
+        assert!(allowed("eventSettings", event(), director()).await);
+        assert!(allowed("applications", event(), director()).await);
+        assert!(!allowed("eventSettings", organization(), director()).await);
+        assert!(!allowed("applications", organization(), director()).await);
+        assert!(!allowed("applications", Scope::User, director()).await);
+    }
+
+    #[tokio::test]
+    async fn organization_roles_only_apply_to_organizations() {
+        let director = || user(UserRole::Director);
+
+        assert!(allowed("members", organization(), director()).await);
+        assert!(allowed("reports", organization(), director()).await);
+        assert!(!allowed("members", event(), director()).await);
+        assert!(!allowed("reports", event(), director()).await);
+    }
+
+    #[tokio::test]
+    async fn has_at_least_role_follows_ordering() {
+        for (role, expected) in [
+            (UserRole::Participant, false),
+            (UserRole::Volunteer, false),
+            (UserRole::Organizer, true),
+            (UserRole::Owner, true),
+            (UserRole::Unknown(String::from("sponsor")), false),
+        ] {
+            let allowed = allowed("applications", event(), user(role.clone())).await;
+            assert_eq!(allowed, expected, "{role:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn has_permission_uses_default_mapping() {
+        assert!(allowed("checkIn", event(), user(UserRole::Volunteer)).await);
+        assert!(allowed("checkIn", organization(), user(UserRole::Organizer)).await);
+        assert!(!allowed("checkIn", event(), user(UserRole::Participant)).await);
+        assert!(!allowed("checkIn", Scope::User, user(UserRole::Owner)).await);
+    }
+
+    #[tokio::test]
+    async fn has_permission_uses_mapping_from_context() {
+        let permissions =
+            RolePermissions::empty().grant(UserRole::Participant, Permission::CheckIn);
+        let request = |role| {
+            Request::new("{ checkIn }")
+                .data(event())
+                .data(user(role))
+                .data(permissions.clone())
+        };
+
+        assert!(succeeds(request(UserRole::Participant)).await);
+        assert!(!succeeds(request(UserRole::Volunteer)).await);
+        assert!(!succeeds(request(UserRole::Owner)).await);
+    }
+
+    #[tokio::test]
+    async fn expired_role_is_denied() {
+        let mut user = user(UserRole::Organizer);
+        if let User::Authenticated(context) = &mut user {
+            context.role_window = Some(RoleWindow {
+                valid_from: from_unix_seconds(1_700_000_000),
+                valid_until: from_unix_seconds(1_700_172_800),
+            });
+        }
+
+        for (seconds, allowed) in [
+            (1_699_999_999, false),
+            (1_700_086_400, true),
+            (1_700_172_800, false),
+        ] {
+            let clock: Arc<dyn Clock> = Arc::new(FixedClock::new(from_unix_seconds(seconds)));
+            let request = Request::new("{ applications }")
+                .data(event())
+                .data(user.clone())
+                .data(clock);
+
+            assert_eq!(succeeds(request).await, allowed, "{seconds}");
+        }
+    }
 }
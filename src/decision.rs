@@ -0,0 +1,316 @@
+#[cfg(any(feature = "axum", feature = "graphql"))]
+use crate::{
+    scope::{EventScope, OrganizationScope, Scope},
+    user::{AuthenticatedUser, ParticipantStatus, ServiceUser, User, UserRole},
+};
+use serde::Serialize;
+#[cfg(any(feature = "axum", feature = "graphql"))]
+use std::time::SystemTime;
+
+/// The outcome of an authorization check, along with the conditions that led to it
+///
+/// Checks stop at the first condition that fails, so the failed conditions are always the last
+/// ones evaluated.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Decision {
+    allowed: bool,
+    evaluated: Vec<String>,
+    failed: Vec<String>,
+}
+
+impl Decision {
+    /// Start a decision with no conditions evaluated
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of a condition, returning `None` if it failed
+    ///
+    /// Designed to be used with `?` in functions returning an [`Option`].
+    pub fn require(&mut self, condition: impl Into<String>, passed: bool) -> Option<()> {
+        let condition = condition.into();
+        if !passed {
+            self.allowed = false;
+            self.failed.push(condition.clone());
+        }
+        self.evaluated.push(condition);
+
+        passed.then_some(())
+    }
+
+    /// Whether the request is allowed
+    pub fn is_allowed(&self) -> bool {
+        self.allowed
+    }
+
+    /// The conditions that were evaluated, in order
+    pub fn evaluated(&self) -> &[String] {
+        &self.evaluated
+    }
+
+    /// The conditions that did not pass
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+}
+
+/// Conditions shared by the checks
+#[cfg(any(feature = "axum", feature = "graphql"))]
+impl Decision {
+    /// Require the user to be authenticated
+    pub(crate) fn authenticated<'u>(&mut self, user: &'u User) -> Option<&'u AuthenticatedUser> {
+        let user = match user {
+            User::Authenticated(context) => Some(context),
+            _ => None,
+        };
+        self.require("authenticated", user.is_some())?;
+        user
+    }
+
+    /// Require the request to be made by a service
+    pub(crate) fn service<'u>(&mut self, user: &'u User) -> Option<&'u ServiceUser> {
+        let service = match user {
+            User::Service(context) => Some(context),
+            _ => None,
+        };
+        self.require("service", service.is_some())?;
+        service
+    }
+
+    /// Require the request to be scoped to an event
+    pub(crate) fn event<'s>(&mut self, scope: &'s Scope) -> Option<&'s EventScope> {
+        let event = match scope {
+            Scope::Event(context) => Some(context),
+            _ => None,
+        };
+        self.require("event scope", event.is_some())?;
+        event
+    }
+
+    /// Require the request to be scoped to an organization
+    pub(crate) fn organization<'s>(&mut self, scope: &'s Scope) -> Option<&'s OrganizationScope> {
+        let organization = match scope {
+            Scope::Organization(context) => Some(context),
+            _ => None,
+        };
+        self.require("organization scope", organization.is_some())?;
+        organization
+    }
+
+    /// Get the user's role for the event the request is scoped to
+    ///
+    /// Roles outside of their window are treated as no role.
+    #[cfg(feature = "graphql")]
+    pub(crate) fn event_role(
+        &mut self,
+        scope: &Scope,
+        user: &User,
+        now: SystemTime,
+    ) -> Option<Option<UserRole>> {
+        self.event(scope)?;
+        let user = self.authenticated(user)?;
+
+        Some(user.role_for_at(scope, now))
+    }
+
+    /// Get the user's role for the organization the request is scoped to
+    ///
+    /// Roles outside of their window are treated as no role.
+    #[cfg(feature = "graphql")]
+    pub(crate) fn organization_role(
+        &mut self,
+        scope: &Scope,
+        user: &User,
+        now: SystemTime,
+    ) -> Option<Option<UserRole>> {
+        self.organization(scope)?;
+        let user = self.authenticated(user)?;
+
+        Some(user.role_for_at(scope, now))
+    }
+
+    /// Get the user's role for the event or organization the request is scoped to
+    ///
+    /// Roles outside of their window are treated as no role.
+    pub(crate) fn scoped_role(
+        &mut self,
+        scope: &Scope,
+        user: &User,
+        now: SystemTime,
+    ) -> Option<Option<UserRole>> {
+        self.require(
+            "event or organization scope",
+            matches!(scope, Scope::Event(_) | Scope::Organization(_)),
+        )?;
+        let user = self.authenticated(user)?;
+
+        Some(user.role_for_at(scope, now))
+    }
+
+    /// Require the user to be a participant of the event with one of the statuses
+    pub(crate) fn participant_status(
+        &mut self,
+        scope: &Scope,
+        user: &User,
+        statuses: &[ParticipantStatus],
+    ) -> Option<ParticipantStatus> {
+        self.event(scope)?;
+        let user = self.authenticated(user)?;
+
+        let status = user
+            .participant_status
+            .filter(|status| statuses.contains(status));
+        let names = statuses
+            .iter()
+            .map(ParticipantStatus::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.require(
+            format!("participant status is one of {names}"),
+            status.is_some(),
+        )?;
+        status
+    }
+}
+
+impl Default for Decision {
+    fn default() -> Self {
+        Self {
+            allowed: true,
+            evaluated: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+/// Who can see why a request was denied
+///
+/// Explanations are only ever shown to admins since they reveal how access is controlled.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Explanations {
+    /// Never include the explanation
+    #[default]
+    Hidden,
+    /// Include the explanation when the user is an admin
+    Admins,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decision;
+
+    #[test]
+    fn records_conditions() {
+        let mut decision = Decision::new();
+        assert!(decision.is_allowed());
+
+        assert_eq!(decision.require("authenticated", true), Some(()));
+        assert!(decision.is_allowed());
+        assert_eq!(decision.require("admin", false), None);
+        assert!(!decision.is_allowed());
+
+        assert_eq!(decision.evaluated(), ["authenticated", "admin"]);
+        assert_eq!(decision.failed(), ["admin"]);
+    }
+
+    #[test]
+    fn serializes() {
+        let mut decision = Decision::new();
+        decision.require("authenticated", true);
+        decision.require("role at least organizer", false);
+
+        assert_eq!(
+            serde_json::to_string(&decision).unwrap(),
+            r#"{"allowed":false,"evaluated":["authenticated","role at least organizer"],"failed":["role at least organizer"]}"#
+        );
+    }
+}
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql_tests {
+    use super::{Decision, Explanations};
+    use crate::{
+        checks::{guard_where, has_at_least_role},
+        testing::fixtures,
+        EventScope, OrganizationId, Scope, Slug, User, UserRole,
+    };
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema, ServerError};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+        async fn applications(&self) -> i32 {
+            1
+        }
+    }
+
+    fn user(is_admin: bool) -> User {
+        let user = fixtures::volunteer();
+        if is_admin { user.admin() } else { user }.build()
+    }
+
+    async fn execute(explanations: Option<Explanations>, user: User) -> ServerError {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription).finish();
+        let scope = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(1),
+            domain: None,
+        });
+
+        let mut request = Request::new("{ applications }").data(scope).data(user);
+        if let Some(explanations) = explanations {
+            request = request.data(explanations);
+        }
+
+        let mut response = schema.execute(request).await;
+        assert_eq!(response.errors.len(), 1);
+        response.errors.remove(0)
+    }
+
+    fn expected() -> Decision {
+        let mut decision = Decision::new();
+        decision.require("event scope", true);
+        decision.require("authenticated", true);
+        decision.require("role at least organizer", false);
+        decision
+    }
+
+    #[tokio::test]
+    async fn denial_carries_decision() {
+        let error = execute(None, user(true)).await;
+        assert_eq!(error.source::<Decision>(), Some(&expected()));
+
+        let extensions = error.extensions.unwrap();
+        assert!(extensions.get("explanation").is_none());
+    }
+
+    #[tokio::test]
+    async fn explains_to_admins_when_enabled() {
+        let error = execute(Some(Explanations::Admins), user(true)).await;
+
+        let extensions = error.extensions.unwrap();
+        assert_eq!(
+            extensions.get("explanation"),
+            Some(&async_graphql::to_value(expected()).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn hides_explanation_from_non_admins() {
+        for (explanations, is_admin) in [
+            (Some(Explanations::Admins), false),
+            (Some(Explanations::Hidden), true),
+        ] {
+            let error = execute(explanations, user(is_admin)).await;
+
+            let extensions = error.extensions.unwrap();
+            assert!(extensions.get("explanation").is_none());
+            assert_eq!(
+                extensions.get("code"),
+                Some(&async_graphql::Value::from("FORBIDDEN"))
+            );
+        }
+    }
+}
@@ -1,23 +1,68 @@
 //! Typed headers for passing context information
-use crate::user::UserRole;
+//!
+//! # Compatibility
+//!
+//! Every writer tags the context with a `Context-Version` header containing the
+//! [`PROTOCOL_VERSION`] it implements. Contexts without the header were written before versioning
+//! was introduced and are treated as version `0`. The following rules let services be upgraded
+//! independently of each other:
+//!
+//! - Headers a reader does not know about are always ignored, so new optional headers can be
+//!   added without bumping the version.
+//! - Adding a value to an enumerated header (i.e. a new `User-Session` or `User-Role`) requires
+//!   bumping the version. When a reader encounters a value it does not understand in a context
+//!   written by a newer version, it falls back to the least privileged interpretation:
+//!   - `User-Session` is read as unauthenticated
+//!   - `Request-Scope` is read as user scoped
+//!   - `Service-Permissions` is read as granting nothing
+//!   - `User-Memberships` is read without the memberships that have an unknown role
+//!   - optional headers, such as `User-Role`, `User-Participant-Status`, `Auth-Assurance-Level`,
+//!     and `Session-Provider`, are read as absent
+//! - Values that cannot be understood in a context written by the same or an older version are
+//!   still rejected, as they indicate a malformed or forged context.
+//! - Readers can opt into keeping unknown `User-Session`, `Request-Scope`, and `User-Role` values
+//!   regardless of the version with [`Config::unknown_values`]. Unknown sessions and scopes are
+//!   still read as unauthenticated and user scoped, while unknown roles are kept as
+//!   [`UserRole::Unknown`] and never satisfy a role or permission check.
+//! - Removing a header or changing the format of an existing value is a breaking change and must
+//!   be coordinated across all services.
+//!
+//! ## Versions
+//!
+//! 1. Initial versioned protocol
+//! 2. Adds the `owner` and `volunteer` roles, and the `manage-billing` and `delete-organization`
+//!    permissions
+//! 3. Adds the `User-Role-Valid-From` and `User-Role-Valid-Until` headers. Writers omit roles that
+//!    are outside of their window so readers that predate them never see an inactive role
+use crate::{
+    id::RawId,
+    permission::Permission,
+    time::{to_unix_seconds, try_from_unix_seconds},
+    user::{AssuranceLevel, EventMembership, ParticipantStatus, UserRole},
+    validated::{is_token, Slug, ValidationError},
+};
 #[cfg(feature = "axum")]
 use axum_core::response::{IntoResponse, Response};
 use headers::{Header, HeaderMapExt, HeaderName, HeaderValue};
-use http::HeaderMap;
+use http::{header::InvalidHeaderName, HeaderMap};
 use std::{
     borrow::Borrow,
     fmt::{Display, Formatter},
     iter,
     ops::Deref,
+    time::SystemTime,
 };
 
 static EVENT_DOMAIN: HeaderName = HeaderName::from_static("event-domain");
 static EVENT_SLUG: HeaderName = HeaderName::from_static("event-slug");
 static EVENT_ORGANIZATION_ID: HeaderName = HeaderName::from_static("event-organization-id");
+static ORGANIZATION_ID: HeaderName = HeaderName::from_static("organization-id");
 static USER_SESSION: HeaderName = HeaderName::from_static("user-session");
 static OAUTH_PROVIDER_SLUG: HeaderName = HeaderName::from_static("oauth-provider-slug");
 static OAUTH_USER_ID: HeaderName = HeaderName::from_static("oauth-user-id");
 static OAUTH_USER_EMAIL: HeaderName = HeaderName::from_static("oauth-user-email");
+static SERVICE_NAME: HeaderName = HeaderName::from_static("service-name");
+static SERVICE_PERMISSIONS: HeaderName = HeaderName::from_static("service-permissions");
 static REQUEST_SCOPE: HeaderName = HeaderName::from_static("request-scope");
 static USER_ID: HeaderName = HeaderName::from_static("user-id");
 static USER_GIVEN_NAME: HeaderName = HeaderName::from_static("user-given-name");
@@ -25,11 +70,248 @@ static USER_FAMILY_NAME: HeaderName = HeaderName::from_static("user-family-name"
 static USER_EMAIL: HeaderName = HeaderName::from_static("user-email");
 static USER_ROLE: HeaderName = HeaderName::from_static("user-role");
 static USER_IS_ADMIN: HeaderName = HeaderName::from_static("user-is-admin");
+static USER_MEMBERSHIPS: HeaderName = HeaderName::from_static("user-memberships");
+static USER_PARTICIPANT_STATUS: HeaderName = HeaderName::from_static("user-participant-status");
+static USER_ROLE_VALID_FROM: HeaderName = HeaderName::from_static("user-role-valid-from");
+static USER_ROLE_VALID_UNTIL: HeaderName = HeaderName::from_static("user-role-valid-until");
+static SESSION_ID: HeaderName = HeaderName::from_static("session-id");
+static SESSION_CREATED_AT: HeaderName = HeaderName::from_static("session-created-at");
+static SESSION_EXPIRES_AT: HeaderName = HeaderName::from_static("session-expires-at");
+static SESSION_PROVIDER: HeaderName = HeaderName::from_static("session-provider");
+static AUTH_ASSURANCE_LEVEL: HeaderName = HeaderName::from_static("auth-assurance-level");
+static AUTH_AUTHENTICATED_AT: HeaderName = HeaderName::from_static("auth-authenticated-at");
+static IMPERSONATOR_ID: HeaderName = HeaderName::from_static("impersonator-id");
+static IMPERSONATION_REASON: HeaderName = HeaderName::from_static("impersonation-reason");
+static IMPERSONATION_STARTED_AT: HeaderName = HeaderName::from_static("impersonation-started-at");
+static CONTEXT_VERSION: HeaderName = HeaderName::from_static("context-version");
+
+/// The version of the context headers that this crate reads and writes
+///
+/// See the [compatibility policy](self#compatibility) for how the version is used.
+pub const PROTOCOL_VERSION: u16 = 3;
+
+/// The headers that make up a [`Scope`](crate::Scope)
+pub(crate) static SCOPE_HEADERS: [&HeaderName; 6] = [
+    &CONTEXT_VERSION,
+    &REQUEST_SCOPE,
+    &EVENT_DOMAIN,
+    &EVENT_SLUG,
+    &EVENT_ORGANIZATION_ID,
+    &ORGANIZATION_ID,
+];
+
+/// The headers that make up a [`User`](crate::User)
+pub(crate) static USER_HEADERS: [&HeaderName; 26] = [
+    &CONTEXT_VERSION,
+    &USER_SESSION,
+    &OAUTH_PROVIDER_SLUG,
+    &OAUTH_USER_ID,
+    &OAUTH_USER_EMAIL,
+    &SERVICE_NAME,
+    &SERVICE_PERMISSIONS,
+    &USER_ID,
+    &USER_GIVEN_NAME,
+    &USER_FAMILY_NAME,
+    &USER_EMAIL,
+    &USER_ROLE,
+    &USER_IS_ADMIN,
+    &USER_MEMBERSHIPS,
+    &USER_PARTICIPANT_STATUS,
+    &USER_ROLE_VALID_FROM,
+    &USER_ROLE_VALID_UNTIL,
+    &SESSION_ID,
+    &SESSION_CREATED_AT,
+    &SESSION_EXPIRES_AT,
+    &SESSION_PROVIDER,
+    &AUTH_ASSURANCE_LEVEL,
+    &AUTH_AUTHENTICATED_AT,
+    &IMPERSONATOR_ID,
+    &IMPERSONATION_REASON,
+    &IMPERSONATION_STARTED_AT,
+];
+
+/// Options controlling how context is extracted from headers
+///
+/// The axum extractors look for a `Config` in the request extensions, falling back to
+/// [`Config::default`] when none is present.
+#[derive(Clone, Debug)]
+pub struct Config {
+    strict: bool,
+    prefix: Option<String>,
+    unknown_values: bool,
+}
+
+impl Config {
+    /// Create the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether context headers that appear more than once are rejected
+    ///
+    /// Proxies are expected to replace any context headers sent by the client, so multiple values
+    /// usually indicate an attempt to smuggle in a forged context. Enabled by default.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Whether context headers that appear more than once are rejected
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Set a prefix that is prepended to the name of every context header, i.e. `X-THA-`
+    ///
+    /// Once set, only the prefixed headers are read and written, so unprefixed headers with the
+    /// same names can be used by other parties without colliding. Header names are
+    /// case-insensitive, so the prefix is normalized to lowercase.
+    pub fn prefix(mut self, prefix: &str) -> Result<Self, InvalidHeaderName> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.is_empty() {
+            self.prefix = None;
+        } else {
+            HeaderName::from_bytes(prefix.as_bytes())?;
+            self.prefix = Some(prefix);
+        }
+
+        Ok(self)
+    }
+
+    /// The prefix prepended to the name of every context header, if any
+    pub fn header_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Set whether enumerated values that are not understood are kept instead of rejected
+    ///
+    /// This allows readers to tolerate values introduced by newer writers even when the context
+    /// is not tagged with a newer version, see the [compatibility policy](self#compatibility).
+    /// Disabled by default.
+    pub fn unknown_values(mut self, keep: bool) -> Self {
+        self.unknown_values = keep;
+        self
+    }
+
+    /// Whether enumerated values that are not understood are kept instead of rejected
+    pub fn keeps_unknown_values(&self) -> bool {
+        self.unknown_values
+    }
+
+    /// Get the name a context header is transmitted under
+    fn rename(&self, name: &HeaderName) -> HeaderName {
+        match &self.prefix {
+            Some(prefix) => HeaderName::from_bytes(format!("{prefix}{name}").as_bytes())
+                .expect("prefix must be a valid header name"),
+            None => name.clone(),
+        }
+    }
+
+    /// Copy the prefixed context headers into a new map under their canonical names
+    fn unprefix(&self, headers: &HeaderMap, names: &[&'static HeaderName]) -> HeaderMap {
+        let mut canonical = HeaderMap::new();
+        for name in names {
+            for value in headers.get_all(self.rename(name)) {
+                canonical.append(*name, value.clone());
+            }
+        }
+
+        canonical
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            prefix: None,
+            unknown_values: false,
+        }
+    }
+}
+
+/// Write a context to the response headers using the provided configuration
+///
+/// The [`IntoResponseParts`](axum_core::response::IntoResponseParts) and [`IntoResponse`]
+/// implementations on [`Scope`](crate::Scope) and [`User`](crate::User) use the default
+/// configuration. Wrap the context to use another, i.e. one with a prefix:
+///
+/// ```ignore
+/// (WithConfig(scope, &config), "ok")
+/// ```
+#[cfg(feature = "axum")]
+#[derive(Debug)]
+pub struct WithConfig<'c, T>(pub T, pub &'c Config);
+
+/// Extract context from the headers according to the configuration
+pub(crate) fn extract_with<T, F>(
+    headers: &HeaderMap,
+    config: &Config,
+    names: &[&'static HeaderName],
+    extract: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(&HeaderMap) -> Result<T, Error>,
+{
+    let canonical;
+    let headers = match config.prefix {
+        Some(_) => {
+            canonical = config.unprefix(headers, names);
+            &canonical
+        }
+        None => headers,
+    };
+
+    let result = if config.strict {
+        ensure_single(headers, names).and_then(|_| extract(headers))
+    } else {
+        extract(headers)
+    };
+    result.map_err(|e| Error {
+        name: config.rename(&e.name),
+        kind: e.kind,
+    })
+}
+
+/// Write context to the headers according to the configuration
+///
+/// Nothing is written if any of the headers fail to encode.
+pub(crate) fn write_with<F>(
+    headers: &mut HeaderMap,
+    config: &Config,
+    write: F,
+) -> Result<(), EncodeError>
+where
+    F: FnOnce(&mut HeaderMap) -> Result<(), EncodeError>,
+{
+    let mut staged = HeaderMap::new();
+    write(&mut staged).map_err(|e| EncodeError {
+        name: config.rename(&e.name),
+    })?;
+
+    let mut previous = None;
+    for (name, value) in staged {
+        match name {
+            Some(name) => {
+                let name = config.rename(&name);
+                headers.insert(&name, value);
+                previous = Some(name);
+            }
+            None => {
+                if let Some(name) = &previous {
+                    headers.append(name, value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
 
 #[derive(Debug)]
 pub struct Error {
     /// Name of the header that cased the error
-    pub name: &'static HeaderName,
+    pub name: HeaderName,
     /// Reason why the header extraction failed
     pub kind: ErrorKind,
 }
@@ -38,7 +320,17 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match &self.kind {
             ErrorKind::Missing => write!(f, "Header of type `{}` was missing", self.name),
+            ErrorKind::Duplicated => {
+                write!(
+                    f,
+                    "Header of type `{}` was provided multiple times",
+                    self.name
+                )
+            }
             ErrorKind::Error(_) => write!(f, "Header of type `{}` was invalid", self.name),
+            ErrorKind::Invalid(e) => {
+                write!(f, "Header of type `{}` was invalid: {e}", self.name)
+            }
         }
     }
 }
@@ -46,8 +338,9 @@ impl Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self.kind {
-            ErrorKind::Missing => None,
+            ErrorKind::Missing | ErrorKind::Duplicated => None,
             ErrorKind::Error(e) => Some(e),
+            ErrorKind::Invalid(e) => Some(e),
         }
     }
 }
@@ -58,12 +351,10 @@ impl IntoResponse for Error {
         let mut headers = HeaderMap::with_capacity(1);
         headers.typed_insert(headers::ContentType::json());
 
-        (
-            http::StatusCode::BAD_REQUEST,
-            headers,
-            format!(r#"{{"message":"{self}"}}"#),
-        )
-            .into_response()
+        // validation errors can echo parts of the header value, so they must be escaped
+        let body = serde_json::json!({ "message": self.to_string() });
+
+        (http::StatusCode::BAD_REQUEST, headers, body.to_string()).into_response()
     }
 }
 
@@ -71,8 +362,168 @@ impl IntoResponse for Error {
 pub enum ErrorKind {
     /// The header was missing from the request
     Missing,
+    /// The header had multiple values, which is rejected in [strict mode](Config::strict)
+    Duplicated,
     /// An error occurred when parsing the header from the request
     Error(headers::Error),
+    /// The header was well-formed, but its value failed validation
+    Invalid(ValidationError),
+}
+
+/// An error that occurred while writing context to headers
+#[derive(Debug)]
+pub struct EncodeError {
+    /// Name of the header whose value could not be represented
+    pub name: HeaderName,
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Header of type `{}` could not be encoded", self.name)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[cfg(feature = "axum")]
+impl IntoResponse for EncodeError {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::with_capacity(1);
+        headers.typed_insert(headers::ContentType::json());
+
+        let body = serde_json::json!({ "message": self.to_string() });
+
+        (
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            headers,
+            body.to_string(),
+        )
+            .into_response()
+    }
+}
+
+/// A header whose value might not be representable as a [`HeaderValue`]
+pub(crate) trait TryEncode: Header {
+    /// Encode the header, failing if the value cannot be represented
+    fn try_encode(&self) -> Result<HeaderValue, EncodeError>;
+}
+
+/// Insert a header that might not be representable into the map
+pub(crate) fn try_insert<H>(headers: &mut HeaderMap, header: H) -> Result<(), EncodeError>
+where
+    H: TryEncode,
+{
+    let value = header.try_encode()?;
+    headers.insert(H::name(), value);
+    Ok(())
+}
+
+/// Write headers into the map all at once, leaving it untouched if any of them fail to encode
+pub(crate) fn write_atomically<F>(headers: &mut HeaderMap, write: F) -> Result<(), EncodeError>
+where
+    F: FnOnce(&mut HeaderMap) -> Result<(), EncodeError>,
+{
+    let mut staged = HeaderMap::new();
+    write(&mut staged)?;
+    headers.extend(staged);
+    Ok(())
+}
+
+/// Ensure none of the headers appear more than once in the map
+pub(crate) fn ensure_single(
+    headers: &HeaderMap,
+    names: &[&'static HeaderName],
+) -> Result<(), Error> {
+    match names
+        .iter()
+        .find(|name| headers.get_all(**name).iter().nth(1).is_some())
+    {
+        Some(name) => Err(Error {
+            name: (*name).clone(),
+            kind: ErrorKind::Duplicated,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether the context in the headers was written by a newer version of the protocol
+pub(crate) fn written_by_newer(headers: &HeaderMap) -> Result<bool, Error> {
+    let version = extract_opt::<ContextVersion>(headers)?;
+    Ok(version.is_some_and(|version| *version > PROTOCOL_VERSION))
+}
+
+/// Resolve an enumerated header value that was not understood
+///
+/// Unknown values are kept when `keep` is set, treated as absent when the context was written by a
+/// `newer` version of the protocol, and rejected otherwise.
+pub(crate) fn resolve_unknown<H>(value: H, keep: bool, newer: bool) -> Result<Option<H>, Error>
+where
+    H: Header,
+{
+    if keep {
+        Ok(Some(value))
+    } else if newer {
+        Ok(None)
+    } else {
+        Err(Error {
+            name: H::name().clone(),
+            kind: ErrorKind::Error(headers::Error::invalid()),
+        })
+    }
+}
+
+/// Decode an enumerated value that is not known to this version
+fn decode_unknown(value: &HeaderValue) -> Result<String, headers::Error> {
+    match value.to_str() {
+        Ok(value) if is_token(value) => Ok(value.to_owned()),
+        _ => Err(headers::Error::invalid()),
+    }
+}
+
+/// Encode an enumerated value, failing if it is not a valid token
+fn encode_unknown(value: &str) -> Option<HeaderValue> {
+    if is_token(value) {
+        HeaderValue::from_str(value).ok()
+    } else {
+        None
+    }
+}
+
+/// Extract the provided header from the map, using the fallback if the value could not be
+/// understood and `tolerant` is set
+pub(crate) fn extract_or<H>(
+    headers: &HeaderMap,
+    tolerant: bool,
+    fallback: impl FnOnce() -> H,
+) -> Result<H, Error>
+where
+    H: Header,
+{
+    match extract(headers) {
+        Err(Error {
+            kind: ErrorKind::Error(_),
+            ..
+        }) if tolerant => Ok(fallback()),
+        result => result,
+    }
+}
+
+/// Extract the provided header from the map if it exists, treating it as missing if the value
+/// could not be understood and `tolerant` is set
+pub(crate) fn extract_opt_or_none<H>(
+    headers: &HeaderMap,
+    tolerant: bool,
+) -> Result<Option<H>, Error>
+where
+    H: Header,
+{
+    match extract_opt(headers) {
+        Err(Error {
+            kind: ErrorKind::Error(_),
+            ..
+        }) if tolerant => Ok(None),
+        result => result,
+    }
 }
 
 /// Extract the provided header from the map if it exists
@@ -81,7 +532,7 @@ where
     H: Header,
 {
     headers.typed_try_get().map_err(|e| Error {
-        name: H::name(),
+        name: H::name().clone(),
         kind: ErrorKind::Error(e),
     })
 }
@@ -94,16 +545,100 @@ where
     match headers.typed_try_get() {
         Ok(Some(h)) => Ok(h),
         Ok(None) => Err(Error {
-            name: H::name(),
+            name: H::name().clone(),
             kind: ErrorKind::Missing,
         }),
         Err(e) => Err(Error {
-            name: H::name(),
+            name: H::name().clone(),
             kind: ErrorKind::Error(e),
         }),
     }
 }
 
+/// Validate the value of an extracted header
+pub(crate) fn validate<H, T>(header: H) -> Result<T, Error>
+where
+    H: Header + Into<String>,
+    T: TryFrom<String, Error = ValidationError>,
+{
+    T::try_from(header.into()).map_err(|e| Error {
+        name: H::name().clone(),
+        kind: ErrorKind::Invalid(e),
+    })
+}
+
+/// The prefix marking an RFC 8187 extended value
+const UTF8_PREFIX: &str = "UTF-8''";
+
+/// Encode an arbitrary string as a header value
+///
+/// Values made up of only visible ASCII characters are written as-is so they remain readable by
+/// older services. Anything else is written as an [RFC 8187](https://www.rfc-editor.org/rfc/rfc8187)
+/// extended value (`UTF-8''` followed by the percent-encoded bytes), which is always valid ASCII.
+fn encode_utf8(value: &str) -> HeaderValue {
+    let is_plain = value.bytes().all(|b| matches!(b, b' '..=b'~'))
+        && !value.starts_with(' ')
+        && !value.ends_with(' ')
+        && !value
+            .get(..UTF8_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(UTF8_PREFIX));
+    if is_plain {
+        return HeaderValue::from_str(value).expect("visible ascii is always a valid header value");
+    }
+
+    let mut encoded = String::with_capacity(UTF8_PREFIX.len() + value.len() * 3);
+    encoded.push_str(UTF8_PREFIX);
+    for byte in value.bytes() {
+        if is_attr_char(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    HeaderValue::from_str(&encoded).expect("percent-encoded values are always valid ascii")
+}
+
+/// Decode a header value written by [`encode_utf8`], or a raw UTF-8 value from older services
+fn decode_utf8(value: &HeaderValue) -> Result<String, headers::Error> {
+    let bytes = value.as_bytes();
+    let Some(encoded) = bytes
+        .get(..UTF8_PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(UTF8_PREFIX.as_bytes()))
+        .map(|_| &bytes[UTF8_PREFIX.len()..])
+    else {
+        return String::from_utf8(bytes.to_vec()).map_err(|_| headers::Error::invalid());
+    };
+
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.iter();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let high = iter.next().and_then(|&b| (b as char).to_digit(16));
+                let low = iter.next().and_then(|&b| (b as char).to_digit(16));
+                match (high, low) {
+                    (Some(high), Some(low)) => decoded.push((high * 16 + low) as u8),
+                    _ => return Err(headers::Error::invalid()),
+                }
+            }
+            byte if is_attr_char(byte) => decoded.push(byte),
+            _ => return Err(headers::Error::invalid()),
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| headers::Error::invalid())
+}
+
+/// Whether the byte can appear unencoded in an RFC 8187 extended value
+fn is_attr_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~'
+        )
+}
+
 macro_rules! expose_inner {
     ( $target:ident ( $as:ty ) ) => {
         expose_inner!($target ( shared: $as, owned: $as ));
@@ -163,14 +698,30 @@ macro_rules! text_header {
                 Ok(Self(decoded.to_owned()))
             }
 
+            /// # Panics
+            ///
+            /// Panics if the value contains anything but visible ASCII characters, use
+            /// [`User::try_write_headers`](crate::User::try_write_headers) to handle arbitrary
+            /// values.
             fn encode<E>(&self, values: &mut E)
             where
                 E: Extend<HeaderValue>,
             {
-                let value = HeaderValue::try_from(&self.0).expect("must be valid ascii");
+                let value = self.try_encode().expect("must be valid ascii");
                 values.extend(iter::once(value))
             }
         }
+
+        impl TryEncode for $name {
+            fn try_encode(&self) -> Result<HeaderValue, EncodeError> {
+                // header values allow opaque bytes, but they would not survive decoding
+                if !self.0.bytes().all(|b| b == b'\t' || (b' '..=b'~').contains(&b)) {
+                    return Err(EncodeError { name: $header_name.clone() });
+                }
+
+                HeaderValue::from_str(&self.0).map_err(|_| EncodeError { name: $header_name.clone() })
+            }
+        }
     };
     (
         $( #[$attr:meta] )*
@@ -190,18 +741,17 @@ macro_rules! text_header {
             where
                 I: Iterator<Item = &'i HeaderValue>,
             {
-                let value = values.next().ok_or_else(headers::Error::invalid)?.as_bytes();
-                let decoded = std::str::from_utf8(&value).map_err(|_| headers::Error::invalid())?;
+                let value = values.next().ok_or_else(headers::Error::invalid)?;
+                let decoded = decode_utf8(value)?;
 
-                Ok(Self(decoded.to_owned()))
+                Ok(Self(decoded))
             }
 
             fn encode<E>(&self, values: &mut E)
             where
                 E: Extend<HeaderValue>,
             {
-                let value = HeaderValue::from_bytes(&self.0.as_bytes()).expect("must be valid bytes");
-                values.extend(iter::once(value))
+                values.extend(iter::once(encode_utf8(&self.0)))
             }
         }
     };
@@ -219,6 +769,12 @@ macro_rules! text_header {
             }
         }
 
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
         expose_inner!($name(shared: str, owned: String));
     };
 }
@@ -226,16 +782,16 @@ macro_rules! text_header {
 macro_rules! int_header {
     (
         $( #[$attr:meta] )*
-        $name:ident, $header_name:ident
+        $name:ident($int:ty), $header_name:ident
     ) => {
         $( #[$attr] )*
         #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-        pub struct $name(i32);
+        pub struct $name($int);
 
-        expose_inner!($name(i32));
+        expose_inner!($name($int));
 
-        impl From<i32> for $name {
-            fn from(value: i32) -> Self {
+        impl From<$int> for $name {
+            fn from(value: $int) -> Self {
                 Self(value)
             }
         }
@@ -270,6 +826,103 @@ macro_rules! int_header {
     };
 }
 
+macro_rules! user_id_header {
+    (
+        $( #[$attr:meta] )*
+        $name:ident, $header_name:ident
+    ) => {
+        $( #[$attr] )*
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        pub struct $name(crate::UserId);
+
+        expose_inner!($name(crate::UserId));
+
+        impl From<crate::UserId> for $name {
+            fn from(value: crate::UserId) -> Self {
+                Self(value)
+            }
+        }
+
+        impl Header for $name {
+            fn name() -> &'static HeaderName {
+                &$header_name
+            }
+
+            fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+            where
+                I: Iterator<Item = &'i HeaderValue>,
+            {
+                let value = values.next().ok_or_else(headers::Error::invalid)?;
+                let decoded = value
+                    .to_str()
+                    .map_err(|_| headers::Error::invalid())?
+                    .parse()
+                    .map_err(|_| headers::Error::invalid())?;
+
+                Ok(Self(decoded))
+            }
+
+            fn encode<E>(&self, values: &mut E)
+            where
+                E: Extend<HeaderValue>,
+            {
+                let value = HeaderValue::try_from(self.0.to_string())
+                    .expect("user IDs must be valid header values");
+                values.extend(iter::once(value))
+            }
+        }
+    };
+}
+
+macro_rules! timestamp_header {
+    (
+        $( #[$attr:meta] )*
+        $name:ident, $header_name:ident
+    ) => {
+        $( #[$attr] )*
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        pub struct $name(SystemTime);
+
+        expose_inner!($name(SystemTime));
+
+        impl From<SystemTime> for $name {
+            fn from(value: SystemTime) -> Self {
+                Self(value)
+            }
+        }
+
+        impl Header for $name {
+            fn name() -> &'static HeaderName {
+                &$header_name
+            }
+
+            fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+            where
+                I: Iterator<Item = &'i HeaderValue>,
+            {
+                let value = values.next().ok_or_else(headers::Error::invalid)?;
+                let seconds = value
+                    .to_str()
+                    .map_err(|_| headers::Error::invalid())?
+                    .parse()
+                    .map_err(|_| headers::Error::invalid())?;
+
+                try_from_unix_seconds(seconds)
+                    .map(Self)
+                    .ok_or_else(headers::Error::invalid)
+            }
+
+            fn encode<E>(&self, values: &mut E)
+            where
+                E: Extend<HeaderValue>,
+            {
+                let value = HeaderValue::from(to_unix_seconds(self.0));
+                values.extend(iter::once(value))
+            }
+        }
+    };
+}
+
 text_header! {
     /// `Event-Domain` header containing a domain where the event can be found
     ascii EventDomain, EVENT_DOMAIN
@@ -282,11 +935,16 @@ text_header! {
 
 int_header! {
     /// `Event-Organization-ID` header containing the ID of the organization that runs the event
-    EventOrganizationId, EVENT_ORGANIZATION_ID
+    EventOrganizationId(RawId), EVENT_ORGANIZATION_ID
+}
+
+int_header! {
+    /// `Organization-ID` header containing the ID of the organization the request is scoped to
+    OrganizationId(RawId), ORGANIZATION_ID
 }
 
 /// `User-Session` header containing the user's authentication status
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum UserSession {
     /// The user is unauthenticated
     Unauthenticated,
@@ -296,6 +954,10 @@ pub enum UserSession {
     RegistrationNeeded,
     /// The user is fully authenticated
     Authenticated,
+    /// The request is being made by another service
+    Service,
+    /// A session introduced by a newer version of the protocol
+    Unknown(String),
 }
 
 impl Header for UserSession {
@@ -314,25 +976,36 @@ impl Header for UserSession {
             b"oauth" => Ok(Self::OAuth),
             b"registration-needed" => Ok(Self::RegistrationNeeded),
             b"authenticated" => Ok(Self::Authenticated),
-            _ => Err(headers::Error::invalid()),
+            b"service" => Ok(Self::Service),
+            _ => decode_unknown(value).map(Self::Unknown),
         }
     }
 
+    /// # Panics
+    ///
+    /// Panics if an unknown value is not a valid token.
     fn encode<E>(&self, values: &mut E)
     where
         E: Extend<HeaderValue>,
     {
-        let value = HeaderValue::from_static(match self {
-            Self::Unauthenticated => "unauthenticated",
-            Self::OAuth => "oauth",
-            Self::RegistrationNeeded => "registration-needed",
-            Self::Authenticated => "authenticated",
-        });
+        let value = match self {
+            Self::Unauthenticated => HeaderValue::from_static("unauthenticated"),
+            Self::OAuth => HeaderValue::from_static("oauth"),
+            Self::RegistrationNeeded => HeaderValue::from_static("registration-needed"),
+            Self::Authenticated => HeaderValue::from_static("authenticated"),
+            Self::Service => HeaderValue::from_static("service"),
+            Self::Unknown(value) => encode_unknown(value).expect("must be a valid token"),
+        };
 
         values.extend(iter::once(value))
     }
 }
 
+int_header! {
+    /// `Context-Version` header containing the version of the protocol the context was written with
+    ContextVersion(u16), CONTEXT_VERSION
+}
+
 text_header! {
     /// `OAuth-Provider-Slug` header containing the slug of the provider the user used to
     /// authenticate with
@@ -349,8 +1022,63 @@ text_header! {
     utf8 OAuthUserEmail, OAUTH_USER_EMAIL
 }
 
+text_header! {
+    /// `Service-Name` header containing the name of the service making the request
+    ascii ServiceName, SERVICE_NAME
+}
+
+/// `Service-Permissions` header containing the comma-separated permissions granted to the service
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServicePermissions(Vec<Permission>);
+
+impl From<Vec<Permission>> for ServicePermissions {
+    fn from(value: Vec<Permission>) -> Self {
+        Self(value)
+    }
+}
+
+expose_inner!(ServicePermissions(shared: [Permission], owned: Vec<Permission>));
+
+impl Header for ServicePermissions {
+    fn name() -> &'static HeaderName {
+        &SERVICE_PERMISSIONS
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+        let decoded = value.to_str().map_err(|_| headers::Error::invalid())?;
+
+        let permissions = decoded
+            .split(',')
+            .map(str::trim)
+            .filter(|permission| !permission.is_empty())
+            .map(|permission| Permission::parse(permission).ok_or_else(headers::Error::invalid))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self(permissions))
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let encoded = self
+            .0
+            .iter()
+            .map(Permission::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let value = HeaderValue::try_from(encoded).expect("permissions are valid ascii");
+        values.extend(iter::once(value))
+    }
+}
+
 /// `Request-Scope` header containing the desired scope for the request
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum RequestScope {
     /// A request with no restrictions on data access
     Admin,
@@ -362,6 +1090,12 @@ pub enum RequestScope {
     ///
     /// This includes actions like managing an event or submitting an application
     Event,
+    /// A request restricted to the current organization
+    ///
+    /// This includes actions like managing billing or the organization's members
+    Organization,
+    /// A scope introduced by a newer version of the protocol
+    Unknown(String),
 }
 
 impl Header for RequestScope {
@@ -380,16 +1114,22 @@ impl Header for RequestScope {
             b"admin" => Ok(Self::Admin),
             b"user" => Ok(Self::User),
             b"event" => Ok(Self::Event),
-            _ => Err(headers::Error::invalid()),
+            b"organization" => Ok(Self::Organization),
+            _ => decode_unknown(value).map(Self::Unknown),
         }
     }
 
+    /// # Panics
+    ///
+    /// Panics if an unknown value is not a valid token.
     fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
-        let value = HeaderValue::from_static(match self {
-            Self::Admin => "admin",
-            Self::User => "user",
-            Self::Event => "event",
-        });
+        let value = match self {
+            Self::Admin => HeaderValue::from_static("admin"),
+            Self::User => HeaderValue::from_static("user"),
+            Self::Event => HeaderValue::from_static("event"),
+            Self::Organization => HeaderValue::from_static("organization"),
+            Self::Unknown(value) => encode_unknown(value).expect("must be a valid token"),
+        };
 
         values.extend(iter::once(value))
     }
@@ -410,7 +1150,7 @@ text_header! {
     utf8 UserEmail, USER_EMAIL
 }
 
-int_header! {
+user_id_header! {
     /// `User-ID` header containing the user's ID
     UserId, USER_ID
 }
@@ -420,6 +1160,151 @@ impl Header for UserRole {
         &USER_ROLE
     }
 
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        Self: Sized,
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+        let decoded = value.to_str().map_err(|_| headers::Error::invalid())?;
+
+        UserRole::parse(decoded).ok_or_else(headers::Error::invalid)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if an unknown role is not a valid token, use
+    /// [`User::try_write_headers`](crate::User::try_write_headers) to handle arbitrary roles.
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let value = self.try_encode().expect("must be a valid token");
+        values.extend(iter::once(value))
+    }
+}
+
+impl TryEncode for UserRole {
+    fn try_encode(&self) -> Result<HeaderValue, EncodeError> {
+        encode_unknown(self.as_str()).ok_or_else(|| EncodeError {
+            name: USER_ROLE.clone(),
+        })
+    }
+}
+
+/// `User-Memberships` header containing the events the user is a member of
+///
+/// Encoded as a comma-separated list of `slug=role` pairs. The event slugs are only validated when
+/// unwrapping the header, so invalid slugs are reported like any other invalid value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UserMemberships(Vec<(String, UserRole)>);
+
+impl UserMemberships {
+    /// Validate the event slugs and unwrap the memberships
+    pub fn try_into_inner(self) -> Result<Vec<EventMembership>, Error> {
+        self.0
+            .into_iter()
+            .map(|(event, role)| {
+                Ok(EventMembership {
+                    event: Slug::try_from(event)?,
+                    role,
+                })
+            })
+            .collect::<Result<_, ValidationError>>()
+            .map_err(|e| Error {
+                name: USER_MEMBERSHIPS.clone(),
+                kind: ErrorKind::Invalid(e),
+            })
+    }
+
+    /// Whether any of the memberships has a role that was not understood
+    pub(crate) fn has_unknown_role(&self) -> bool {
+        self.0.iter().any(|(_, role)| role.is_unknown())
+    }
+
+    /// Remove the memberships with a role that was not understood
+    pub(crate) fn without_unknown_roles(mut self) -> Self {
+        self.0.retain(|(_, role)| !role.is_unknown());
+        self
+    }
+}
+
+impl From<Vec<EventMembership>> for UserMemberships {
+    fn from(value: Vec<EventMembership>) -> Self {
+        Self(
+            value
+                .into_iter()
+                .map(|membership| (membership.event.into_inner(), membership.role))
+                .collect(),
+        )
+    }
+}
+
+impl Header for UserMemberships {
+    fn name() -> &'static HeaderName {
+        &USER_MEMBERSHIPS
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+        let decoded = value.to_str().map_err(|_| headers::Error::invalid())?;
+
+        let memberships = decoded
+            .split(',')
+            .map(str::trim)
+            .filter(|membership| !membership.is_empty())
+            .map(|membership| {
+                let (event, role) = membership
+                    .split_once('=')
+                    .ok_or_else(headers::Error::invalid)?;
+                let role = UserRole::parse(role).ok_or_else(headers::Error::invalid)?;
+
+                Ok((event.to_owned(), role))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self(memberships))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if an unknown role is not a valid token, use
+    /// [`User::try_write_headers`](crate::User::try_write_headers) to handle arbitrary roles.
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let value = self.try_encode().expect("must be valid ascii");
+        values.extend(iter::once(value))
+    }
+}
+
+impl TryEncode for UserMemberships {
+    fn try_encode(&self) -> Result<HeaderValue, EncodeError> {
+        if !self.0.iter().all(|(_, role)| is_token(role.as_str())) {
+            return Err(EncodeError {
+                name: USER_MEMBERSHIPS.clone(),
+            });
+        }
+
+        let encoded = self
+            .0
+            .iter()
+            .map(|(event, role)| format!("{event}={}", role.as_str()))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        HeaderValue::try_from(encoded).map_err(|_| EncodeError {
+            name: USER_MEMBERSHIPS.clone(),
+        })
+    }
+}
+
+impl Header for ParticipantStatus {
+    fn name() -> &'static HeaderName {
+        &USER_PARTICIPANT_STATUS
+    }
+
     fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
     where
         Self: Sized,
@@ -428,21 +1313,18 @@ impl Header for UserRole {
         let value = values.next().ok_or_else(headers::Error::invalid)?;
 
         match value.as_bytes() {
-            b"director" => Ok(Self::Director),
-            b"manager" => Ok(Self::Manager),
-            b"organizer" => Ok(Self::Organizer),
-            b"participant" => Ok(Self::Participant),
+            b"applied" => Ok(Self::Applied),
+            b"waitlisted" => Ok(Self::Waitlisted),
+            b"rejected" => Ok(Self::Rejected),
+            b"accepted" => Ok(Self::Accepted),
+            b"confirmed" => Ok(Self::Confirmed),
+            b"checked-in" => Ok(Self::CheckedIn),
             _ => Err(headers::Error::invalid()),
         }
     }
 
     fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
-        let value = HeaderValue::from_static(match self {
-            Self::Director => "director",
-            Self::Manager => "manager",
-            Self::Organizer => "organizer",
-            Self::Participant => "participant",
-        });
+        let value = HeaderValue::from_static(self.as_str());
 
         values.extend(iter::once(value))
     }
@@ -486,3 +1368,122 @@ impl Header for UserIsAdmin {
         values.extend(iter::once(value))
     }
 }
+
+user_id_header! {
+    /// `Impersonator-ID` header containing the ID of the user acting on behalf of the authenticated
+    /// user
+    ImpersonatorId, IMPERSONATOR_ID
+}
+
+text_header! {
+    /// `Impersonation-Reason` header containing why the user is being impersonated
+    utf8 ImpersonationReason, IMPERSONATION_REASON
+}
+
+timestamp_header! {
+    /// `Impersonation-Started-At` header containing when the impersonation started, as seconds
+    /// since the Unix epoch
+    ImpersonationStartedAt, IMPERSONATION_STARTED_AT
+}
+
+text_header! {
+    /// `Session-ID` header containing the ID of the user's session
+    ascii SessionId, SESSION_ID
+}
+
+timestamp_header! {
+    /// `User-Role-Valid-From` header containing when the user's role takes effect, as seconds
+    /// since the Unix epoch
+    UserRoleValidFrom, USER_ROLE_VALID_FROM
+}
+
+timestamp_header! {
+    /// `User-Role-Valid-Until` header containing when the user's role expires, as seconds since
+    /// the Unix epoch
+    UserRoleValidUntil, USER_ROLE_VALID_UNTIL
+}
+
+timestamp_header! {
+    /// `Session-Created-At` header containing when the session was created, as seconds since the
+    /// Unix epoch
+    SessionCreatedAt, SESSION_CREATED_AT
+}
+
+timestamp_header! {
+    /// `Session-Expires-At` header containing when the session expires, as seconds since the Unix
+    /// epoch
+    SessionExpiresAt, SESSION_EXPIRES_AT
+}
+
+text_header! {
+    /// `Session-Provider` header containing the slug of the OAuth provider used to log in
+    ascii SessionProvider, SESSION_PROVIDER
+}
+
+impl Header for AssuranceLevel {
+    fn name() -> &'static HeaderName {
+        &AUTH_ASSURANCE_LEVEL
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        Self: Sized,
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let value = values.next().ok_or_else(headers::Error::invalid)?;
+
+        match value.as_bytes() {
+            b"password" => Ok(Self::Password),
+            b"oauth" => Ok(Self::OAuth),
+            b"mfa" => Ok(Self::Mfa),
+            _ => Err(headers::Error::invalid()),
+        }
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        let value = HeaderValue::from_static(self.as_str());
+
+        values.extend(iter::once(value))
+    }
+}
+
+timestamp_header! {
+    /// `Auth-Authenticated-At` header containing when the user last authenticated at their current
+    /// assurance level, as seconds since the Unix epoch
+    AuthAuthenticatedAt, AUTH_AUTHENTICATED_AT
+}
+
+#[cfg(all(test, feature = "axum"))]
+mod axum_tests {
+    use super::{EncodeError, Error, ErrorKind, USER_EMAIL};
+    use crate::ValidationError;
+    use axum::{body::to_bytes, response::IntoResponse};
+
+    async fn body(response: axum::response::Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn error_response_escapes_message() {
+        let error = Error {
+            name: USER_EMAIL.clone(),
+            kind: ErrorKind::Invalid(ValidationError::InvalidCharacter('"')),
+        };
+        let message = error.to_string();
+
+        let body = body(error.into_response()).await;
+        assert_eq!(body, serde_json::json!({ "message": message }));
+    }
+
+    #[tokio::test]
+    async fn encode_error_response_is_json() {
+        let error = EncodeError {
+            name: USER_EMAIL.clone(),
+        };
+        let message = error.to_string();
+
+        let body = body(error.into_response()).await;
+        assert_eq!(body, serde_json::json!({ "message": message }));
+    }
+}
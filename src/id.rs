@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+/// The integer type numeric IDs are stored as
+///
+/// IDs are 32 bits wide unless the `bigint` feature is enabled, in which case they are 64 bits
+/// wide. IDs that fit in 32 bits are transmitted the same way either way.
+#[cfg(not(feature = "bigint"))]
+pub type RawId = i32;
+
+/// The integer type numeric IDs are stored as
+///
+/// IDs are 32 bits wide unless the `bigint` feature is enabled, in which case they are 64 bits
+/// wide. IDs that fit in 32 bits are transmitted the same way either way.
+#[cfg(feature = "bigint")]
+pub type RawId = i64;
+
+macro_rules! id {
+    (
+        $( #[$attr:meta] )*
+        $name:ident($inner:ty)
+    ) => {
+        $( #[$attr] )*
+        #[derive(
+            Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name($inner);
+
+        impl $name {
+            /// Wrap a raw ID
+            pub const fn new(id: $inner) -> Self {
+                Self(id)
+            }
+
+            /// Get the raw ID
+            pub const fn get(self) -> $inner {
+                self.0
+            }
+        }
+
+        impl From<RawId> for $name {
+            fn from(value: RawId) -> Self {
+                Self(value)
+            }
+        }
+
+        #[cfg(feature = "bigint")]
+        impl From<i32> for $name {
+            fn from(value: i32) -> Self {
+                Self(value.into())
+            }
+        }
+
+        impl From<$name> for $inner {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        #[cfg(feature = "graphql")]
+        #[async_graphql::Scalar]
+        impl async_graphql::ScalarType for $name {
+            fn parse(value: async_graphql::Value) -> async_graphql::InputValueResult<Self> {
+                match &value {
+                    async_graphql::Value::Number(number) => number
+                        .as_i64()
+                        .and_then(|id| <$inner>::try_from(id).ok())
+                        .map(Self)
+                        .ok_or_else(|| async_graphql::InputValueError::expected_type(value)),
+                    _ => Err(async_graphql::InputValueError::expected_type(value)),
+                }
+            }
+
+            fn to_value(&self) -> async_graphql::Value {
+                async_graphql::Value::Number(self.0.into())
+            }
+        }
+    };
+}
+
+id! {
+    /// The ID of an organization
+    OrganizationId(RawId)
+}
+
+/// The ID of a user
+///
+/// Users from the original store have numeric [`RawId`]s, while users from the new store are
+/// identified by UUIDs when the `uuid` feature is enabled. Both forms are serialized as-is: numeric
+/// IDs as numbers and UUIDs as hyphenated strings.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum UserId {
+    /// A numeric ID
+    Numeric(RawId),
+    /// A UUID
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid),
+}
+
+impl UserId {
+    /// Wrap a raw numeric ID
+    pub const fn new(id: RawId) -> Self {
+        Self::Numeric(id)
+    }
+
+    /// Get the numeric ID, if the user has one
+    pub const fn as_numeric(&self) -> Option<RawId> {
+        match self {
+            Self::Numeric(id) => Some(*id),
+            #[cfg(feature = "uuid")]
+            Self::Uuid(_) => None,
+        }
+    }
+
+    /// Get the UUID, if the user has one
+    #[cfg(feature = "uuid")]
+    pub const fn as_uuid(&self) -> Option<&uuid::Uuid> {
+        match self {
+            Self::Numeric(_) => None,
+            Self::Uuid(id) => Some(id),
+        }
+    }
+}
+
+impl From<RawId> for UserId {
+    fn from(value: RawId) -> Self {
+        Self::Numeric(value)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<i32> for UserId {
+    fn from(value: i32) -> Self {
+        Self::Numeric(value.into())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for UserId {
+    fn from(value: uuid::Uuid) -> Self {
+        Self::Uuid(value)
+    }
+}
+
+impl Display for UserId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Numeric(id) => Display::fmt(id, f),
+            #[cfg(feature = "uuid")]
+            Self::Uuid(id) => Display::fmt(&id.hyphenated(), f),
+        }
+    }
+}
+
+impl FromStr for UserId {
+    type Err = ParseUserIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse() {
+            return Ok(Self::Numeric(id));
+        }
+
+        #[cfg(feature = "uuid")]
+        if let Ok(id) = s.parse() {
+            return Ok(Self::Uuid(id));
+        }
+
+        Err(ParseUserIdError(()))
+    }
+}
+
+/// The error returned when a [`UserId`] could not be parsed
+#[derive(Debug)]
+pub struct ParseUserIdError(());
+
+impl Display for ParseUserIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid user ID")
+    }
+}
+
+impl Error for ParseUserIdError {}
+
+#[cfg(feature = "graphql")]
+#[async_graphql::Scalar]
+impl async_graphql::ScalarType for UserId {
+    fn parse(value: async_graphql::Value) -> async_graphql::InputValueResult<Self> {
+        match &value {
+            async_graphql::Value::Number(number) => number
+                .as_i64()
+                .and_then(|id| RawId::try_from(id).ok())
+                .map(Self::Numeric)
+                .ok_or_else(|| async_graphql::InputValueError::expected_type(value)),
+            #[cfg(feature = "uuid")]
+            async_graphql::Value::String(id) => id
+                .parse()
+                .map(Self::Uuid)
+                .map_err(|_| async_graphql::InputValueError::expected_type(value)),
+            _ => Err(async_graphql::InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> async_graphql::Value {
+        match self {
+            Self::Numeric(id) => async_graphql::Value::Number((*id).into()),
+            #[cfg(feature = "uuid")]
+            Self::Uuid(id) => async_graphql::Value::String(id.hyphenated().to_string()),
+        }
+    }
+}
+
+// IDs are stored as `INTEGER`s unless the `bigint` feature is enabled, in which case they are
+// stored as `BIGINT`s. UUID user IDs cannot be encoded through these implementations, use
+// `UserId::as_uuid` to bind them to a UUID column instead.
+#[cfg(feature = "sqlx")]
+mod sqlx_impls {
+    use super::{OrganizationId, RawId as Column, UserId};
+    use sqlx::{encode::IsNull, error::BoxDynError, Database, Decode, Encode, Type};
+
+    macro_rules! numeric_column {
+        ($name:ident, |$id:ident| $as_numeric:expr) => {
+            impl<DB> Type<DB> for $name
+            where
+                DB: Database,
+                Column: Type<DB>,
+            {
+                fn type_info() -> DB::TypeInfo {
+                    <Column as Type<DB>>::type_info()
+                }
+
+                fn compatible(ty: &DB::TypeInfo) -> bool {
+                    <Column as Type<DB>>::compatible(ty)
+                }
+            }
+
+            impl<'q, DB> Encode<'q, DB> for $name
+            where
+                DB: Database,
+                Column: Encode<'q, DB>,
+            {
+                fn encode_by_ref(
+                    &self,
+                    buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+                ) -> Result<IsNull, BoxDynError> {
+                    let $id = self;
+                    let column = $as_numeric.ok_or_else(|| format!("ID {self} is not numeric"))?;
+                    column.encode_by_ref(buf)
+                }
+            }
+
+            impl<'r, DB> Decode<'r, DB> for $name
+            where
+                DB: Database,
+                Column: Decode<'r, DB>,
+            {
+                fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+                    Column::decode(value).map(Self::from)
+                }
+            }
+        };
+    }
+
+    numeric_column!(OrganizationId, |id| Some(id.get()));
+    numeric_column!(UserId, |id| id.as_numeric());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrganizationId, UserId};
+
+    #[test]
+    fn serializes_as_number() {
+        let serialized = serde_json::to_string(&UserId::new(42)).unwrap();
+        assert_eq!(serialized, "42");
+
+        let deserialized: OrganizationId = serde_json::from_str("7").unwrap();
+        assert_eq!(deserialized, OrganizationId::new(7));
+    }
+
+    #[test]
+    fn display_and_parse() {
+        let id = UserId::new(1234);
+        assert_eq!(id.to_string(), "1234");
+        assert_eq!("1234".parse::<UserId>().unwrap(), id);
+        assert!("abc".parse::<UserId>().is_err());
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn beyond_32_bits() {
+        let raw = i64::from(i32::MAX) + 1;
+
+        let id: UserId = serde_json::from_str(&raw.to_string()).unwrap();
+        assert_eq!(id, UserId::new(raw));
+        assert_eq!(id.as_numeric(), Some(raw));
+        assert_eq!(serde_json::to_string(&id).unwrap(), raw.to_string());
+
+        let id: OrganizationId = raw.to_string().parse().unwrap();
+        assert_eq!(id.get(), raw);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn beyond_32_bits_requires_bigint() {
+        let raw = (i64::from(i32::MAX) + 1).to_string();
+
+        assert!(serde_json::from_str::<UserId>(&raw).is_err());
+        assert!(raw.parse::<UserId>().is_err());
+        assert!(raw.parse::<OrganizationId>().is_err());
+    }
+
+    #[test]
+    fn from_i32() {
+        assert_eq!(UserId::from(55), UserId::new(55));
+        assert_eq!(OrganizationId::from(55), OrganizationId::new(55));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        let raw = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let uuid = raw.parse::<uuid::Uuid>().unwrap();
+
+        let id: UserId = raw.parse().unwrap();
+        assert_eq!(id, UserId::from(uuid));
+        assert_eq!(id.as_uuid(), Some(&uuid));
+        assert_eq!(id.as_numeric(), None);
+        assert_eq!(id.to_string(), raw);
+
+        let serialized = serde_json::to_string(&id).unwrap();
+        assert_eq!(serialized, format!("\"{raw}\""));
+        let deserialized: UserId = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, id);
+    }
+}
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql_tests {
+    use super::{OrganizationId, UserId};
+    use async_graphql::{value, EmptyMutation, EmptySubscription, Object, Schema};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn echo(&self, id: UserId) -> UserId {
+            id
+        }
+
+        async fn organization(&self, id: OrganizationId) -> OrganizationId {
+            id
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trips_as_scalar() {
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+
+        let response = schema.execute("{ echo(id: 42) organization(id: 7) }").await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data, value!({ "echo": 42, "organization": 7 }));
+
+        let response = schema.execute("{ organization(id: 3000000000) }").await;
+        #[cfg(feature = "bigint")]
+        assert_eq!(response.data, value!({ "organization": 3000000000i64 }));
+        #[cfg(not(feature = "bigint"))]
+        assert!(!response.errors.is_empty());
+
+        let response = schema.execute("{ echo(id: \"42\") }").await;
+        assert!(!response.errors.is_empty());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[tokio::test]
+    async fn uuid_as_scalar() {
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+
+        let response = schema
+            .execute(r#"{ echo(id: "67e55044-10b1-426f-9247-bb680e5fe0c8") }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data,
+            value!({ "echo": "67e55044-10b1-426f-9247-bb680e5fe0c8" })
+        );
+    }
+}
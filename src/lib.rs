@@ -4,37 +4,71 @@
 //! contains information about how the request is being made (i.e. where is it from, is it for a particular event).
 //! Whereas the [`User`] contains information about who is making the request.
 
+#[cfg(feature = "graphql")]
+pub mod audit;
+#[cfg(feature = "axum")]
+pub mod axum_checks;
 #[cfg(feature = "graphql")]
 pub mod checks;
 #[cfg(feature = "headers")]
 pub mod headers;
+#[cfg(feature = "policy")]
+pub mod policy;
+#[cfg(any(feature = "axum", feature = "graphql"))]
+pub mod relationship;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
+mod decision;
+mod id;
+mod permission;
 mod scope;
+mod time;
 mod user;
+mod validated;
 
 #[cfg(feature = "graphql")]
 pub use checks::guard;
+pub use decision::{Decision, Explanations};
 #[cfg(feature = "headers")]
-pub use headers::Error;
-pub use scope::{EventScope, Scope, ScopeParams};
-pub use user::{AuthenticatedUser, User, UserParams, UserRegistrationNeeded, UserRole};
+pub use headers::{EncodeError, Error};
+pub use id::{OrganizationId, ParseUserIdError, RawId, UserId};
+pub use permission::{Permission, RolePermissions};
+pub use scope::{EventScope, OrganizationScope, Scope, ScopeParams};
+pub use time::{Clock, FixedClock, SystemClock};
+pub use user::{
+    Assurance, AssuranceLevel, AuthenticatedUser, EventMembership, Impersonator, ParticipantStatus,
+    RoleWindow, ServiceUser, SessionMetadata, User, UserParams, UserRegistrationNeeded, UserRole,
+};
+pub use validated::{Email, OAuthProvider, Slug, ValidationError};
 
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
 mod test_util {
+    /// Build a [`HeaderMap`](http::HeaderMap) from header names and values
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value is not a valid header value.
     #[macro_export]
     macro_rules! headers {
         () => {
-            ::http::header::HeaderMap::with_capacity(0)
+            $crate::__private::http::header::HeaderMap::with_capacity(0)
         };
         (
             $( $name:expr => $value:expr ),* $(,)?
         ) => {{
-            let mut headers = ::http::header::HeaderMap::new();
-            $(headers.insert($name, ::http::header::HeaderValue::try_from($value).unwrap());)*
+            let mut headers = $crate::__private::http::header::HeaderMap::new();
+            $(headers.insert(
+                $name,
+                $crate::__private::http::header::HeaderValue::try_from($value).unwrap(),
+            );)*
             headers
         }};
     }
 
+    /// Generate tests asserting that extracting the context from the headers fails
+    ///
+    /// Each case checks the name of the offending header and the [`ErrorKind`](crate::headers::ErrorKind).
     #[macro_export]
     macro_rules! error_test_cases {
         (
@@ -58,4 +92,42 @@ mod test_util {
             )+
         };
     }
+
+    /// Generate a deterministic set of arbitrary strings, biased towards characters that are
+    /// troublesome in headers
+    #[cfg(all(test, feature = "headers"))]
+    pub(crate) fn arbitrary_strings() -> impl Iterator<Item = String> {
+        const INTERESTING: &[char] = &[
+            ' ', '\t', '\r', '\n', '\0', '\u{7f}', '%', '\'', '"', ',', '=', '@', '-', 'é', 'ß',
+            '名', '🧇', '\u{200b}', '\u{ff}',
+        ];
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        (0..512).map(move |_| {
+            let length = next() % 12;
+            (0..length)
+                .map(|_| {
+                    let n = next();
+                    match n % 3 {
+                        0 => INTERESTING[(n / 3) as usize % INTERESTING.len()],
+                        1 => char::from(b' ' + (n / 3 % 95) as u8),
+                        _ => char::from_u32((n / 3 % 0x3000) as u32).unwrap_or('?'),
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(all(any(test, feature = "testing"), feature = "headers"))]
+#[doc(hidden)]
+pub mod __private {
+    pub use http;
 }
@@ -0,0 +1,277 @@
+use crate::user::UserRole;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A fine-grained action that can be granted to a role
+///
+/// (De)serialized by the name it is transmitted with, see [`Permission::as_str`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Permission {
+    /// Submit and edit their own application to the event
+    SubmitApplication,
+    /// Check participants in at the event
+    CheckIn,
+    /// Read the applications submitted to the event
+    ReadApplications,
+    /// Accept, reject, or waitlist applications
+    ReviewApplications,
+    /// Modify the event's settings
+    ManageEvent,
+    /// Modify the organization's settings
+    ManageOrganization,
+    /// Add, remove, or change the roles of members of the organization
+    ManageMembers,
+    /// Modify the organization's billing information
+    ManageBilling,
+    /// Delete the organization and all of its events
+    DeleteOrganization,
+}
+
+impl Permission {
+    /// Every permission, in order
+    const ALL: [Self; 9] = [
+        Self::SubmitApplication,
+        Self::CheckIn,
+        Self::ReadApplications,
+        Self::ReviewApplications,
+        Self::ManageEvent,
+        Self::ManageOrganization,
+        Self::ManageMembers,
+        Self::ManageBilling,
+        Self::DeleteOrganization,
+    ];
+
+    /// The name of the permission as it is transmitted
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SubmitApplication => "submit-application",
+            Self::CheckIn => "check-in",
+            Self::ReadApplications => "read-applications",
+            Self::ReviewApplications => "review-applications",
+            Self::ManageEvent => "manage-event",
+            Self::ManageOrganization => "manage-organization",
+            Self::ManageMembers => "manage-members",
+            Self::ManageBilling => "manage-billing",
+            Self::DeleteOrganization => "delete-organization",
+        }
+    }
+
+    /// Parse a permission from its transmitted name
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == value)
+    }
+}
+
+impl Serialize for Permission {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Permission {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Self::parse(&name).ok_or_else(|| D::Error::custom(format!("unknown permission `{name}`")))
+    }
+}
+
+/// The permissions granted to each role
+///
+/// The [`Default`] mapping mirrors the ordering of [`UserRole`], with each role being granted all
+/// the permissions of the roles below it.
+///
+/// (De)serialized as an object from the transmitted name of each role to the transmitted names of
+/// its permissions, i.e. `{"organizer": ["check-in"]}`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct RolePermissions(BTreeMap<UserRole, BTreeSet<Permission>>);
+
+impl RolePermissions {
+    /// Create a mapping where no role is granted any permission
+    pub fn empty() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Grant a permission to a role
+    pub fn grant(mut self, role: UserRole, permission: Permission) -> Self {
+        self.0.entry(role).or_default().insert(permission);
+        self
+    }
+
+    /// Revoke a permission from a role
+    pub fn revoke(mut self, role: UserRole, permission: Permission) -> Self {
+        if let Some(permissions) = self.0.get_mut(&role) {
+            permissions.remove(&permission);
+        }
+        self
+    }
+
+    /// Get the permissions granted to a role
+    pub fn permissions(&self, role: &UserRole) -> impl Iterator<Item = Permission> + '_ {
+        self.0.get(role).into_iter().flatten().copied()
+    }
+
+    /// Check whether the role is granted the permission
+    pub fn allows(&self, role: &UserRole, permission: Permission) -> bool {
+        self.0
+            .get(role)
+            .map(|permissions| permissions.contains(&permission))
+            .unwrap_or(false)
+    }
+
+    /// A shared instance of the default mapping
+    #[cfg(feature = "graphql")]
+    pub(crate) fn default_ref() -> &'static Self {
+        static DEFAULT: std::sync::OnceLock<RolePermissions> = std::sync::OnceLock::new();
+        DEFAULT.get_or_init(Self::default)
+    }
+}
+
+impl Default for RolePermissions {
+    fn default() -> Self {
+        let ladder: [(UserRole, &[Permission]); 6] = [
+            (UserRole::Participant, &[Permission::SubmitApplication]),
+            (UserRole::Volunteer, &[Permission::CheckIn]),
+            (
+                UserRole::Organizer,
+                &[Permission::ReadApplications, Permission::ReviewApplications],
+            ),
+            (
+                UserRole::Manager,
+                &[Permission::ManageEvent, Permission::ManageOrganization],
+            ),
+            (UserRole::Director, &[Permission::ManageMembers]),
+            (
+                UserRole::Owner,
+                &[Permission::ManageBilling, Permission::DeleteOrganization],
+            ),
+        ];
+
+        let mut mapping = Self::empty();
+        let mut granted = Vec::new();
+        for (role, permissions) in ladder {
+            granted.extend_from_slice(permissions);
+            for permission in &granted {
+                mapping = mapping.grant(role.clone(), *permission);
+            }
+        }
+
+        mapping
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, RolePermissions};
+    use crate::UserRole;
+
+    #[test]
+    fn default_mapping_follows_role_ordering() {
+        let mapping = RolePermissions::default();
+
+        assert!(mapping.allows(&UserRole::Participant, Permission::SubmitApplication));
+        assert!(!mapping.allows(&UserRole::Participant, Permission::CheckIn));
+        assert!(mapping.allows(&UserRole::Organizer, Permission::CheckIn));
+        assert!(mapping.allows(&UserRole::Organizer, Permission::ReadApplications));
+        assert!(!mapping.allows(&UserRole::Organizer, Permission::ManageEvent));
+        assert!(mapping.allows(&UserRole::Manager, Permission::ManageOrganization));
+        assert!(!mapping.allows(&UserRole::Manager, Permission::ManageMembers));
+        assert!(mapping.allows(&UserRole::Director, Permission::ManageMembers));
+        assert!(mapping.allows(&UserRole::Director, Permission::SubmitApplication));
+        assert!(!mapping.allows(&UserRole::Director, Permission::ManageBilling));
+        assert!(!mapping.allows(&UserRole::Director, Permission::DeleteOrganization));
+        assert!(mapping.allows(&UserRole::Owner, Permission::ManageBilling));
+        assert!(mapping.allows(&UserRole::Owner, Permission::DeleteOrganization));
+        assert!(mapping.allows(&UserRole::Owner, Permission::ManageMembers));
+    }
+
+    #[test]
+    fn volunteers_can_only_check_in() {
+        let mapping = RolePermissions::default();
+
+        assert!(mapping.allows(&UserRole::Volunteer, Permission::CheckIn));
+        assert!(mapping.allows(&UserRole::Volunteer, Permission::SubmitApplication));
+        assert!(!mapping.allows(&UserRole::Volunteer, Permission::ReadApplications));
+        assert!(!mapping.allows(&UserRole::Volunteer, Permission::ReviewApplications));
+    }
+
+    #[test]
+    fn unknown_role_is_denied() {
+        let mapping = RolePermissions::default();
+        let role = UserRole::Unknown("sponsor".to_owned());
+
+        assert!(!mapping.allows(&role, Permission::SubmitApplication));
+        assert_eq!(mapping.permissions(&role).count(), 0);
+    }
+
+    #[test]
+    fn revoke_permission() {
+        let mapping =
+            RolePermissions::default().revoke(UserRole::Organizer, Permission::ReadApplications);
+
+        assert!(mapping.allows(&UserRole::Organizer, Permission::CheckIn));
+        assert!(!mapping.allows(&UserRole::Organizer, Permission::ReadApplications));
+        assert!(mapping.allows(&UserRole::Manager, Permission::ReadApplications));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for permission in Permission::ALL {
+            assert_eq!(Permission::parse(permission.as_str()), Some(permission));
+
+            let json = serde_json::to_string(&permission).unwrap();
+            assert_eq!(json, format!("\"{}\"", permission.as_str()));
+            assert_eq!(
+                serde_json::from_str::<Permission>(&json).unwrap(),
+                permission
+            );
+        }
+
+        assert_eq!(Permission::parse("CheckIn"), None);
+        assert!(serde_json::from_str::<Permission>(r#""CheckIn""#).is_err());
+    }
+
+    #[test]
+    fn deserialize_mapping() {
+        let mapping: RolePermissions = serde_json::from_str(
+            r#"{"organizer":["check-in"],"manager":["check-in","read-applications"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            mapping,
+            RolePermissions::empty()
+                .grant(UserRole::Organizer, Permission::CheckIn)
+                .grant(UserRole::Manager, Permission::CheckIn)
+                .grant(UserRole::Manager, Permission::ReadApplications)
+        );
+    }
+
+    #[test]
+    fn serialize_mapping() {
+        let mapping = RolePermissions::empty()
+            .grant(UserRole::Organizer, Permission::CheckIn)
+            .grant(
+                UserRole::Unknown(String::from("sponsor")),
+                Permission::ReadApplications,
+            );
+
+        let json = serde_json::to_string(&mapping).unwrap();
+        assert_eq!(
+            json,
+            r#"{"sponsor":["read-applications"],"organizer":["check-in"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<RolePermissions>(&json).unwrap(),
+            mapping
+        );
+    }
+}
@@ -0,0 +1,1033 @@
+//! Authorization policies loaded from TOML or JSON files
+//!
+//! A [`Policy`] is a list of [`Rule`]s, each granting an action on a resource when all of its
+//! conditions are met. Requests are denied unless a rule for the resource and action is
+//! satisfied, so changing who can do what only requires updating the policy file.
+//!
+//! ```toml
+//! [[rules]]
+//! resource = "applications"
+//! action = "read"
+//! scope = ["event"]
+//! min_role = "organizer"
+//!
+//! [[rules]]
+//! resource = "applications"
+//! action = "update"
+//! scope = ["event"]
+//! participant_status = ["applied", "waitlisted"]
+//!
+//! [[rules]]
+//! resource = "*"
+//! action = "*"
+//! admin = true
+//! ```
+//!
+//! Policies can be enforced with [`is_allowed`] as a GraphQL guard, or with a [`PolicyLayer`] in
+//! axum. Use [`PolicyTests`] to check a policy file against a set of expected decisions.
+//!
+//! Roles, permissions, and participant statuses are always spelled the way they are transmitted,
+//! i.e. `organizer` or `checked-in`, in both policies and their tests.
+
+#[cfg(feature = "axum")]
+use crate::{
+    axum_checks::Forbidden,
+    decision::{Decision, Explanations},
+    headers::Config,
+    time::{Clock, SystemClock},
+};
+use crate::{
+    scope::Scope,
+    user::{ParticipantStatus, User, UserRole},
+};
+#[cfg(feature = "axum")]
+use axum_core::response::{IntoResponse, Response};
+#[cfg(feature = "axum")]
+use http::Request;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    fs, io,
+    path::Path,
+    time::SystemTime,
+};
+#[cfg(feature = "axum")]
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// A set of rules deciding which actions are allowed on which resources
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// The rules granting access
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Create a policy from a list of rules
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    /// Parse a policy from TOML
+    pub fn from_toml(source: &str) -> Result<Self, LoadError> {
+        toml::from_str(source).map_err(LoadError::Toml)
+    }
+
+    /// Parse a policy from JSON
+    pub fn from_json(source: &str) -> Result<Self, LoadError> {
+        serde_json::from_str(source).map_err(LoadError::Json)
+    }
+
+    /// Load a policy from a file, using the extension to determine the format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        load(path.as_ref(), Self::from_toml, Self::from_json)
+    }
+
+    /// Whether the action on the resource is allowed for the request
+    pub fn allows(&self, resource: &str, action: &str, scope: &Scope, user: &User) -> bool {
+        self.allows_at(resource, action, scope, user, SystemTime::now())
+    }
+
+    /// Whether the action on the resource is allowed for the request at a point in time
+    pub fn allows_at(
+        &self,
+        resource: &str,
+        action: &str,
+        scope: &Scope,
+        user: &User,
+        now: SystemTime,
+    ) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| rule.applies_to(resource, action))
+            .any(|rule| rule.is_satisfied(scope, user, now))
+    }
+}
+
+/// Grants an action on a resource when all of the conditions are met
+///
+/// Conditions that are not set always pass. Rules that check the user's role, admin status, or
+/// participant status are only ever satisfied by authenticated users, and rules that check the
+/// participant status only by requests scoped to an event.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// The resource the rule applies to, or `*` for every resource
+    pub resource: String,
+    /// The action the rule grants, or `*` for every action
+    pub action: String,
+    /// The kinds of scope the request must be made in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<ScopeKind>>,
+    /// The minimum role the user must have for the scope, by its transmitted name
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "option_role_name"
+    )]
+    pub min_role: Option<UserRole>,
+    /// Whether the user must, or must not, be an admin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<bool>,
+    /// The statuses the user must have as a participant of the event the request is scoped to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub participant_status: Option<Vec<ParticipantStatus>>,
+}
+
+impl Rule {
+    /// Whether the rule covers the action on the resource
+    fn applies_to(&self, resource: &str, action: &str) -> bool {
+        (self.resource == "*" || self.resource == resource)
+            && (self.action == "*" || self.action == action)
+    }
+
+    /// Whether the request meets all of the rule's conditions
+    fn is_satisfied(&self, scope: &Scope, user: &User, now: SystemTime) -> bool {
+        if let Some(kinds) = &self.scope {
+            if !kinds.contains(&ScopeKind::from(scope)) {
+                return false;
+            }
+        }
+
+        if self.min_role.is_none() && self.admin.is_none() && self.participant_status.is_none() {
+            return true;
+        }
+        let User::Authenticated(user) = user else {
+            return false;
+        };
+
+        if let Some(admin) = self.admin {
+            if user.is_admin != admin {
+                return false;
+            }
+        }
+        if let Some(min_role) = &self.min_role {
+            match user.role_for_at(scope, now) {
+                Some(role) if role.is_at_least(min_role) => {}
+                _ => return false,
+            }
+        }
+        if let Some(statuses) = &self.participant_status {
+            if !matches!(scope, Scope::Event(_)) {
+                return false;
+            }
+            match user.participant_status {
+                Some(status) if statuses.contains(&status) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// The kind of [`Scope`] a request was made in
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeKind {
+    /// See [`Scope::Admin`]
+    Admin,
+    /// See [`Scope::User`]
+    User,
+    /// See [`Scope::Event`]
+    Event,
+    /// See [`Scope::Organization`]
+    Organization,
+}
+
+impl From<&Scope> for ScopeKind {
+    fn from(scope: &Scope) -> Self {
+        match scope {
+            Scope::Admin => Self::Admin,
+            Scope::User => Self::User,
+            Scope::Event(_) => Self::Event,
+            Scope::Organization(_) => Self::Organization,
+        }
+    }
+}
+
+/// (De)serialize an optional [`UserRole`] by the name it is transmitted with
+///
+/// Unknown roles are rejected since they can never be satisfied.
+mod option_role_name {
+    use crate::user::UserRole;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(role: &Option<UserRole>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match role {
+            Some(role) => serializer.serialize_str(role.as_str()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<UserRole>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(name) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        UserRole::parse_known(&name)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("unknown role `{name}`")))
+    }
+}
+
+/// An error that occurred while loading a policy or its tests
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file's extension was not `.toml` or `.json`
+    UnknownFormat,
+    /// The TOML was invalid
+    Toml(toml::de::Error),
+    /// The JSON was invalid
+    Json(serde_json::Error),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read file: {e}"),
+            Self::UnknownFormat => f.write_str("file must have a .toml or .json extension"),
+            Self::Toml(e) => write!(f, "invalid TOML: {e}"),
+            Self::Json(e) => write!(f, "invalid JSON: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::UnknownFormat => None,
+            Self::Toml(e) => Some(e),
+            Self::Json(e) => Some(e),
+        }
+    }
+}
+
+/// Read a file and parse it according to its extension
+fn load<T>(
+    path: &Path,
+    from_toml: fn(&str) -> Result<T, LoadError>,
+    from_json: fn(&str) -> Result<T, LoadError>,
+) -> Result<T, LoadError> {
+    let parse = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => from_toml,
+        Some("json") => from_json,
+        _ => return Err(LoadError::UnknownFormat),
+    };
+
+    let source = fs::read_to_string(path).map_err(LoadError::Io)?;
+    parse(&source)
+}
+
+/// Expected decisions for a policy, loaded from TOML or JSON
+///
+/// Each case describes a request and whether it should be allowed:
+///
+/// ```toml
+/// [[cases]]
+/// name = "organizers can read applications"
+/// resource = "applications"
+/// action = "read"
+/// allow = true
+/// scope = { kind = "event", event = "wafflehacks", organization_id = 1 }
+/// user = { type = "authenticated", id = 1, given_name = "John", family_name = "Doe", email = "john.doe@gmail.com", role = "organizer", is_admin = false }
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyTests {
+    #[serde(default)]
+    cases: Vec<PolicyTestCase>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyTestCase {
+    name: String,
+    resource: String,
+    action: String,
+    scope: Scope,
+    user: User,
+    allow: bool,
+    /// When the request is made, as seconds since the Unix epoch
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::time::option_unix_seconds"
+    )]
+    at: Option<SystemTime>,
+}
+
+impl PolicyTests {
+    /// Parse the tests from TOML
+    pub fn from_toml(source: &str) -> Result<Self, LoadError> {
+        toml::from_str(source).map_err(LoadError::Toml)
+    }
+
+    /// Parse the tests from JSON
+    pub fn from_json(source: &str) -> Result<Self, LoadError> {
+        serde_json::from_str(source).map_err(LoadError::Json)
+    }
+
+    /// Load the tests from a file, using the extension to determine the format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        load(path.as_ref(), Self::from_toml, Self::from_json)
+    }
+
+    /// The number of cases
+    pub fn len(&self) -> usize {
+        self.cases.len()
+    }
+
+    /// Whether there are no cases
+    pub fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+
+    /// Evaluate every case against the policy, returning the ones that did not match
+    pub fn run(&self, policy: &Policy) -> Result<(), Vec<PolicyTestFailure>> {
+        let failures = self
+            .cases
+            .iter()
+            .filter_map(|case| {
+                let now = case.at.unwrap_or_else(SystemTime::now);
+                let allowed =
+                    policy.allows_at(&case.resource, &case.action, &case.scope, &case.user, now);
+
+                (allowed != case.allow).then(|| PolicyTestFailure {
+                    name: case.name.clone(),
+                    expected: case.allow,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    /// Evaluate every case against the policy
+    ///
+    /// # Panics
+    ///
+    /// Panics listing every case that did not match, for use in tests.
+    pub fn assert_passes(&self, policy: &Policy) {
+        if let Err(failures) = self.run(policy) {
+            let failures = failures
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n  ");
+            panic!("policy tests failed:\n  {failures}");
+        }
+    }
+}
+
+/// A policy test case whose decision did not match
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PolicyTestFailure {
+    /// The name of the case
+    pub name: String,
+    /// Whether the request was expected to be allowed
+    pub expected: bool,
+}
+
+impl Display for PolicyTestFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (expected, actual) = match self.expected {
+            true => ("allowed", "denied"),
+            false => ("denied", "allowed"),
+        };
+        write!(f, "`{}`: expected {expected}, but was {actual}", self.name)
+    }
+}
+
+impl std::error::Error for PolicyTestFailure {}
+
+/// The condition recorded in a [`Decision`](crate::Decision) for a policy check
+#[cfg(any(feature = "axum", feature = "graphql"))]
+fn policy_condition(resource: &str, action: &str) -> String {
+    format!("policy allows {action} on {resource}")
+}
+
+/// Ensure the [`Policy`] in the context data allows the action on the resource
+///
+/// Requests are denied if no policy was provided. Use with
+/// [`guard_where`](crate::checks::guard_where):
+///
+/// ```ignore
+/// #[graphql(guard = r#"guard_where(policy::is_allowed, ("applications", "read"))"#)]
+/// ```
+#[cfg(feature = "graphql")]
+pub fn is_allowed(
+    ctx: &async_graphql::Context<'_>,
+    (resource, action): (&'static str, &'static str),
+) -> async_graphql::Result<()> {
+    let scope = ctx.data_unchecked::<Scope>();
+    let user = ctx.data_unchecked::<User>();
+
+    crate::checks::decide(ctx, |decision| {
+        let policy = ctx.data_opt::<Policy>();
+        decision.require("policy provided", policy.is_some())?;
+
+        let allowed = policy?.allows_at(resource, action, scope, user, crate::checks::now(ctx));
+        decision.require(policy_condition(resource, action), allowed)
+    })
+}
+
+/// Enforce a [`Policy`] for every request to the wrapped service
+///
+/// The [`Scope`] and [`User`] are extracted from the request headers using the [`Config`] in the
+/// request extensions, if any. Requests that are not allowed are rejected with [`Forbidden`],
+/// which only explains the decision if enabled with [`PolicyLayer::explanations`].
+#[cfg(feature = "axum")]
+#[derive(Clone)]
+pub struct PolicyLayer {
+    policy: Arc<Policy>,
+    resource: &'static str,
+    action: &'static str,
+    clock: Arc<dyn Clock>,
+    explanations: Explanations,
+}
+
+#[cfg(feature = "axum")]
+impl PolicyLayer {
+    /// Require the policy to allow the action on the resource
+    pub fn new(policy: Arc<Policy>, resource: &'static str, action: &'static str) -> Self {
+        Self {
+            policy,
+            resource,
+            action,
+            clock: Arc::new(SystemClock),
+            explanations: Explanations::default(),
+        }
+    }
+
+    /// Read the time from the clock instead of the system time
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set who can see why a request was rejected
+    pub fn explanations(mut self, explanations: Explanations) -> Self {
+        self.explanations = explanations;
+        self
+    }
+
+    /// Decide whether the request is allowed, producing the rejection if not
+    fn reject(&self, headers: &http::HeaderMap, config: &Config) -> Option<Response> {
+        let scope = match Scope::from_headers(headers, config) {
+            Ok(scope) => scope,
+            Err(e) => return Some(e.into_response()),
+        };
+        let user = match User::from_headers(headers, config) {
+            Ok(user) => user,
+            Err(e) => return Some(e.into_response()),
+        };
+
+        let now = self.clock.now();
+        let mut decision = Decision::new();
+        let allowed = self
+            .policy
+            .allows_at(self.resource, self.action, &scope, &user, now);
+
+        if decision
+            .require(policy_condition(self.resource, self.action), allowed)
+            .is_some()
+        {
+            return None;
+        }
+
+        let forbidden = match self.explanations {
+            Explanations::Admins => Forbidden::new(decision).explain_to(&user),
+            Explanations::Hidden => Forbidden::new(decision),
+        };
+        Some(forbidden.into_response())
+    }
+}
+
+#[cfg(feature = "axum")]
+impl<S> tower_layer::Layer<S> for PolicyLayer {
+    type Service = PolicyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PolicyService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service produced by a [`PolicyLayer`]
+#[cfg(feature = "axum")]
+#[derive(Clone)]
+pub struct PolicyService<S> {
+    inner: S,
+    layer: PolicyLayer,
+}
+
+#[cfg(feature = "axum")]
+impl<S, B> tower_service::Service<Request<B>> for PolicyService<S>
+where
+    S: tower_service::Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let rejection = match request.extensions().get::<Config>() {
+            Some(config) => self.layer.reject(request.headers(), config),
+            None => self.layer.reject(request.headers(), &Config::default()),
+        };
+
+        match rejection {
+            Some(rejection) => Box::pin(async move { Ok(rejection) }),
+            None => Box::pin(self.inner.call(request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadError, Policy, PolicyTestFailure, PolicyTests, Rule, ScopeKind};
+    use crate::{
+        time::from_unix_seconds, AuthenticatedUser, EventScope, OrganizationId, OrganizationScope,
+        ParticipantStatus, RoleWindow, Scope, Slug, User, UserRole,
+    };
+
+    const POLICY: &str = r#"
+        [[rules]]
+        resource = "applications"
+        action = "read"
+        scope = ["event"]
+        min_role = "organizer"
+
+        [[rules]]
+        resource = "applications"
+        action = "update"
+        scope = ["event"]
+        participant_status = ["applied", "waitlisted"]
+
+        [[rules]]
+        resource = "billing"
+        action = "*"
+        scope = ["organization"]
+        min_role = "owner"
+
+        [[rules]]
+        resource = "*"
+        action = "*"
+        admin = true
+    "#;
+
+    fn event() -> Scope {
+        Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(1),
+            domain: None,
+        })
+    }
+
+    fn organization() -> Scope {
+        Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(1),
+        })
+    }
+
+    fn user(role: Option<UserRole>) -> AuthenticatedUser {
+        let user = User::authenticated();
+        match role {
+            Some(role) => user.role(role).into(),
+            None => user.into(),
+        }
+    }
+
+    #[test]
+    fn parse_toml_and_json() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+        assert_eq!(policy.rules.len(), 4);
+        assert_eq!(
+            policy.rules[0],
+            Rule {
+                resource: String::from("applications"),
+                action: String::from("read"),
+                scope: Some(vec![ScopeKind::Event]),
+                min_role: Some(UserRole::Organizer),
+                admin: None,
+                participant_status: None,
+            }
+        );
+
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(Policy::from_json(&json).unwrap(), policy);
+    }
+
+    #[test]
+    fn min_role_uses_transmitted_names() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+        let json = serde_json::to_value(&policy.rules[0]).unwrap();
+        assert_eq!(json["min_role"], "organizer");
+
+        for name in ["Organizer", "founder"] {
+            let err = Policy::from_toml(&format!(
+                r#"
+                [[rules]]
+                resource = "applications"
+                action = "read"
+                min_role = "{name}"
+                "#
+            ))
+            .unwrap_err();
+            assert!(matches!(err, LoadError::Toml(_)), "{name}");
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let err = Policy::from_toml(
+            r#"
+            [[rules]]
+            resource = "applications"
+            action = "read"
+            min_rol = "organizer"
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, LoadError::Toml(_)));
+    }
+
+    #[test]
+    fn load_requires_known_extension() {
+        let err = Policy::load("policy.yaml").unwrap_err();
+        assert!(matches!(err, LoadError::UnknownFormat));
+    }
+
+    #[test]
+    fn role_conditions() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+
+        let organizer = User::Authenticated(user(Some(UserRole::Organizer)));
+        assert!(policy.allows("applications", "read", &event(), &organizer));
+        assert!(!policy.allows("applications", "read", &organization(), &organizer));
+        assert!(!policy.allows("applications", "delete", &event(), &organizer));
+
+        let volunteer = User::Authenticated(user(Some(UserRole::Volunteer)));
+        assert!(!policy.allows("applications", "read", &event(), &volunteer));
+
+        let owner = User::Authenticated(user(Some(UserRole::Owner)));
+        assert!(policy.allows("billing", "update", &organization(), &owner));
+        let director = User::Authenticated(user(Some(UserRole::Director)));
+        assert!(!policy.allows("billing", "update", &organization(), &director));
+
+        assert!(!policy.allows("applications", "read", &event(), &User::Unauthenticated));
+    }
+
+    #[test]
+    fn admin_condition() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+
+        let mut admin = user(None);
+        admin.is_admin = true;
+        let admin = User::Authenticated(admin);
+        assert!(policy.allows("anything", "delete", &Scope::Admin, &admin));
+        assert!(!policy.allows(
+            "anything",
+            "delete",
+            &Scope::Admin,
+            &User::Authenticated(user(None))
+        ));
+    }
+
+    #[test]
+    fn participant_status_condition() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+
+        let mut participant = user(Some(UserRole::Participant));
+        participant.participant_status = Some(ParticipantStatus::Applied);
+        let applied = User::Authenticated(participant.clone());
+        assert!(policy.allows("applications", "update", &event(), &applied));
+
+        participant.participant_status = Some(ParticipantStatus::Accepted);
+        let accepted = User::Authenticated(participant);
+        assert!(!policy.allows("applications", "update", &event(), &accepted));
+    }
+
+    #[test]
+    fn participant_status_requires_event_scope() {
+        let policy = Policy::from_toml(
+            r#"
+            [[rules]]
+            resource = "applications"
+            action = "read"
+            participant_status = ["accepted"]
+            "#,
+        )
+        .unwrap();
+
+        let mut participant = user(Some(UserRole::Participant));
+        participant.participant_status = Some(ParticipantStatus::Accepted);
+        let accepted = User::Authenticated(participant);
+
+        assert!(policy.allows("applications", "read", &event(), &accepted));
+        for scope in [organization(), Scope::User, Scope::Admin] {
+            assert!(!policy.allows("applications", "read", &scope, &accepted));
+        }
+    }
+
+    #[test]
+    fn expired_roles_are_ignored() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+
+        let mut organizer = user(Some(UserRole::Organizer));
+        organizer.role_window = Some(RoleWindow {
+            valid_from: from_unix_seconds(1_700_000_000),
+            valid_until: from_unix_seconds(1_700_172_800),
+        });
+        let organizer = User::Authenticated(organizer);
+
+        let during = from_unix_seconds(1_700_086_400);
+        assert!(policy.allows_at("applications", "read", &event(), &organizer, during));
+        let after = from_unix_seconds(1_700_172_800);
+        assert!(!policy.allows_at("applications", "read", &event(), &organizer, after));
+    }
+
+    #[test]
+    fn empty_policy_denies() {
+        let policy = Policy::default();
+        assert!(!policy.allows("applications", "read", &Scope::User, &User::Unauthenticated));
+    }
+
+    const TESTS: &str = r#"
+        [[cases]]
+        name = "organizers can read applications"
+        resource = "applications"
+        action = "read"
+        allow = true
+        scope = { kind = "event", event = "wafflehacks", organization_id = 1 }
+        user = { type = "authenticated", id = 1, given_name = "John", family_name = "Doe", email = "john.doe@gmail.com", role = "organizer", is_admin = false }
+
+        [[cases]]
+        name = "expired organizers cannot read applications"
+        resource = "applications"
+        action = "read"
+        allow = false
+        at = 1700172800
+        scope = { kind = "event", event = "wafflehacks", organization_id = 1 }
+        user = { type = "authenticated", id = 1, given_name = "John", family_name = "Doe", email = "john.doe@gmail.com", role = "organizer", role_window = { valid_from = 1700000000, valid_until = 1700172800 }, is_admin = false }
+
+        [[cases]]
+        name = "anonymous users cannot read applications"
+        resource = "applications"
+        action = "read"
+        allow = false
+        scope = { kind = "event", event = "wafflehacks", organization_id = 1 }
+        user = { type = "unauthenticated" }
+    "#;
+
+    #[test]
+    fn policy_tests_pass() {
+        let policy = Policy::from_toml(POLICY).unwrap();
+        let tests = PolicyTests::from_toml(TESTS).unwrap();
+
+        assert_eq!(tests.len(), 3);
+        tests.assert_passes(&policy);
+    }
+
+    #[test]
+    fn policy_tests_reject_out_of_range_times() {
+        let tests = format!(
+            r#"{{"cases":[{{"name":"far future","resource":"applications","action":"read","allow":false,"at":{},"scope":{{"kind":"user"}},"user":{{"type":"unauthenticated"}}}}]}}"#,
+            u64::MAX
+        );
+
+        let err = PolicyTests::from_json(&tests).unwrap_err();
+        assert!(matches!(err, LoadError::Json(_)));
+    }
+
+    #[test]
+    fn policy_tests_report_failures() {
+        let tests = PolicyTests::from_toml(TESTS).unwrap();
+
+        let failures = tests.run(&Policy::default()).unwrap_err();
+        assert_eq!(
+            failures,
+            vec![PolicyTestFailure {
+                name: String::from("organizers can read applications"),
+                expected: true,
+            }]
+        );
+        assert_eq!(
+            failures[0].to_string(),
+            "`organizers can read applications`: expected allowed, but was denied"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "policy tests failed")]
+    fn policy_tests_assert_panics() {
+        let tests = PolicyTests::from_toml(TESTS).unwrap();
+        tests.assert_passes(&Policy::default());
+    }
+}
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql_tests {
+    use super::{is_allowed, Policy};
+    use crate::{checks::guard_where, EventScope, OrganizationId, Scope, Slug, User, UserRole};
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        #[graphql(guard = r#"guard_where(is_allowed, ("applications", "read"))"#)]
+        async fn applications(&self) -> i32 {
+            1
+        }
+    }
+
+    fn user(role: UserRole) -> User {
+        User::authenticated().role(role).build()
+    }
+
+    async fn execute(policy: Option<Policy>, user: User) -> bool {
+        let mut schema = Schema::build(Query, EmptyMutation, EmptySubscription);
+        if let Some(policy) = policy {
+            schema = schema.data(policy);
+        }
+
+        let scope = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(1),
+            domain: None,
+        });
+        let request = Request::new("{ applications }").data(scope).data(user);
+        schema.finish().execute(request).await.errors.is_empty()
+    }
+
+    #[tokio::test]
+    async fn guard_uses_policy() {
+        let policy = Policy::from_toml(
+            r#"
+            [[rules]]
+            resource = "applications"
+            action = "read"
+            min_role = "organizer"
+            "#,
+        )
+        .unwrap();
+
+        assert!(execute(Some(policy.clone()), user(UserRole::Manager)).await);
+        assert!(!execute(Some(policy), user(UserRole::Volunteer)).await);
+        assert!(!execute(None, user(UserRole::Manager)).await);
+    }
+}
+
+#[cfg(all(test, feature = "axum"))]
+mod axum_tests {
+    use super::{Policy, PolicyLayer};
+    use crate::{time::from_unix_seconds, Explanations, FixedClock};
+    use axum_core::response::Response;
+    use http::{Request, StatusCode};
+    use std::{convert::Infallible, sync::Arc};
+    use tower::{service_fn, ServiceExt};
+    use tower_layer::Layer;
+
+    fn layer() -> PolicyLayer {
+        let policy = Policy::from_toml(
+            r#"
+            [[rules]]
+            resource = "applications"
+            action = "read"
+            scope = ["event"]
+            min_role = "organizer"
+            "#,
+        )
+        .unwrap();
+
+        PolicyLayer::new(Arc::new(policy), "applications", "read")
+            .clock(Arc::new(FixedClock::new(from_unix_seconds(1_700_086_400))))
+    }
+
+    async fn respond(layer: PolicyLayer, request: Request<()>) -> Response {
+        let service = layer.layer(service_fn(|_: Request<()>| async {
+            Ok::<_, Infallible>(Response::new(axum_core::body::Body::empty()))
+        }));
+
+        service.oneshot(request).await.unwrap()
+    }
+
+    async fn call(request: Request<()>) -> StatusCode {
+        respond(layer(), request).await.status()
+    }
+
+    async fn explanation(layer: PolicyLayer, is_admin: &str) -> Option<serde_json::Value> {
+        let request = request("volunteer")
+            .header("User-Is-Admin", is_admin)
+            .body(())
+            .unwrap();
+        let response = respond(layer, request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        body.as_object_mut().unwrap().remove("explanation")
+    }
+
+    fn request(role: &str) -> http::request::Builder {
+        Request::builder()
+            .header("Request-Scope", "event")
+            .header("Event-Slug", "wafflehacks")
+            .header("Event-Organization-ID", "1")
+            .header("User-Session", "authenticated")
+            .header("User-ID", "1")
+            .header("User-Given-Name", "John")
+            .header("User-Family-Name", "Doe")
+            .header("User-Email", "john.doe@gmail.com")
+            .header("User-Role", role)
+    }
+
+    #[tokio::test]
+    async fn allows_matching_requests() {
+        let status = call(
+            request("organizer")
+                .header("User-Is-Admin", "false")
+                .body(())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rejects_denied_requests() {
+        let status = call(
+            request("volunteer")
+                .header("User-Is-Admin", "false")
+                .body(())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_roles() {
+        let request = request("organizer")
+            .header("User-Is-Admin", "false")
+            .header("User-Role-Valid-From", "1700000000")
+            .header("User-Role-Valid-Until", "1700003600")
+            .body(())
+            .unwrap();
+
+        assert_eq!(call(request).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn explains_rejections_to_admins_when_enabled() {
+        let explaining = || layer().explanations(Explanations::Admins);
+
+        let explained = explanation(explaining(), "true").await.unwrap();
+        assert_eq!(
+            explained["failed"],
+            serde_json::json!(["policy allows read on applications"])
+        );
+        assert_eq!(explanation(explaining(), "false").await, None);
+        assert_eq!(explanation(layer(), "true").await, None);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_context() {
+        let request = Request::builder()
+            .header("Request-Scope", "galaxy")
+            .body(())
+            .unwrap();
+
+        assert_eq!(call(request).await, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,684 @@
+//! Relationship-based checks between users and resources
+//!
+//! Some checks depend on data the [`Scope`](crate::Scope) and [`User`](crate::User) don't carry,
+//! like whether the user owns an application or is on a team. These relationships are looked up
+//! through a [`Relationship`] resolver, usually backed by the service's database.
+//!
+//! Lookups are made through [`Relationships`], which remembers the answers for the lifetime of a
+//! request so the same relationship is never fetched twice, even by concurrent checks. Register
+//! the [`RelationshipsExtension`] on a GraphQL schema or the [`RelationshipsLayer`] on an axum
+//! router to create one for every request, and use the `has_relationship` checks in
+//! [`checks`](crate::checks) and [`axum_checks`](crate::axum_checks), or the `RelationshipGuard` in
+//! [`checks`](crate::checks).
+
+use crate::id::UserId;
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display, Formatter},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::OnceCell;
+
+/// A reference to a resource that a user can have a relationship with
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Resource {
+    /// The kind of resource, i.e. `application` or `team`
+    pub kind: String,
+    /// The resource's identifier
+    pub id: String,
+}
+
+impl Resource {
+    /// Reference a resource by its kind and identifier
+    pub fn new(kind: impl Into<String>, id: impl ToString) -> Self {
+        Self {
+            kind: kind.into(),
+            id: id.to_string(),
+        }
+    }
+}
+
+impl Display for Resource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.kind, self.id)
+    }
+}
+
+/// Resolves whether a user has a relationship with a resource
+#[async_trait]
+pub trait Relationship: Send + Sync + 'static {
+    /// Whether the user has the relation, i.e. `owner` or `member`, with the resource
+    async fn has_relation(
+        &self,
+        user: UserId,
+        relation: &str,
+        resource: &Resource,
+    ) -> Result<bool, RelationshipError>;
+}
+
+#[async_trait]
+impl<R> Relationship for Arc<R>
+where
+    R: Relationship,
+{
+    async fn has_relation(
+        &self,
+        user: UserId,
+        relation: &str,
+        resource: &Resource,
+    ) -> Result<bool, RelationshipError> {
+        (**self).has_relation(user, relation, resource).await
+    }
+}
+
+/// An error that occurred while resolving a relationship
+#[derive(Debug)]
+pub struct RelationshipError(Box<dyn std::error::Error + Send + Sync>);
+
+impl RelationshipError {
+    /// Wrap the error raised by the resolver
+    pub fn new(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self(error.into())
+    }
+}
+
+impl Display for RelationshipError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to resolve relationship: {}", self.0)
+    }
+}
+
+impl std::error::Error for RelationshipError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+#[cfg(feature = "axum")]
+impl axum_core::response::IntoResponse for RelationshipError {
+    fn into_response(self) -> axum_core::response::Response {
+        use headers::HeaderMapExt;
+
+        let mut headers = http::HeaderMap::with_capacity(1);
+        headers.typed_insert(headers::ContentType::json());
+
+        (
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            headers,
+            r#"{"message":"failed to resolve relationship","code":"INTERNAL_SERVER_ERROR"}"#,
+        )
+            .into_response()
+    }
+}
+
+type CacheKey = (UserId, String, Resource);
+
+/// Looks up relationships through a resolver, remembering the answers
+///
+/// Create one for each request; clones share the same answers. Concurrent lookups of the same
+/// relationship wait on a single call to the resolver.
+#[derive(Clone)]
+pub struct Relationships {
+    resolver: Arc<dyn Relationship>,
+    cache: Arc<Mutex<HashMap<CacheKey, Arc<OnceCell<bool>>>>>,
+}
+
+impl Relationships {
+    /// Look up relationships through the resolver
+    pub fn new(resolver: Arc<dyn Relationship>) -> Self {
+        Self {
+            resolver,
+            cache: Arc::default(),
+        }
+    }
+
+    /// Whether the user has the relation with the resource
+    ///
+    /// Only successful lookups are remembered, so failures are retried on the next call.
+    pub async fn has_relation(
+        &self,
+        user: UserId,
+        relation: &str,
+        resource: &Resource,
+    ) -> Result<bool, RelationshipError> {
+        let key = (user, relation.to_owned(), resource.clone());
+        let cell = self
+            .cache
+            .lock()
+            .expect("lock must not be poisoned")
+            .entry(key)
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| self.resolver.has_relation(user, relation, resource))
+            .await
+            .copied()
+    }
+}
+
+impl Debug for Relationships {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Relationships")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Relationship`] resolver that keeps all the relationships in memory
+///
+/// Mostly useful for testing.
+#[derive(Debug, Default)]
+pub struct InMemoryRelationships {
+    relations: Mutex<HashSet<CacheKey>>,
+    lookups: AtomicUsize,
+}
+
+impl InMemoryRelationships {
+    /// Give the user the relation with the resource
+    pub fn grant(&self, user: UserId, relation: &str, resource: Resource) {
+        self.relations
+            .lock()
+            .expect("lock must not be poisoned")
+            .insert((user, relation.to_owned(), resource));
+    }
+
+    /// Remove the user's relation with the resource
+    pub fn revoke(&self, user: UserId, relation: &str, resource: Resource) {
+        self.relations
+            .lock()
+            .expect("lock must not be poisoned")
+            .remove(&(user, relation.to_owned(), resource));
+    }
+
+    /// The number of times a relationship was resolved
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl Relationship for InMemoryRelationships {
+    async fn has_relation(
+        &self,
+        user: UserId,
+        relation: &str,
+        resource: &Resource,
+    ) -> Result<bool, RelationshipError> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+
+        let key = (user, relation.to_owned(), resource.clone());
+        Ok(self
+            .relations
+            .lock()
+            .expect("lock must not be poisoned")
+            .contains(&key))
+    }
+}
+
+/// An extension that makes [`Relationships`] available in the context data of every request
+#[cfg(feature = "graphql")]
+pub struct RelationshipsExtension {
+    resolver: Arc<dyn Relationship>,
+}
+
+#[cfg(feature = "graphql")]
+impl RelationshipsExtension {
+    /// Create a new extension looking up relationships through the resolver
+    pub fn new<R>(resolver: R) -> Self
+    where
+        R: Relationship,
+    {
+        Self {
+            resolver: Arc::new(resolver),
+        }
+    }
+}
+
+#[cfg(feature = "graphql")]
+impl async_graphql::extensions::ExtensionFactory for RelationshipsExtension {
+    fn create(&self) -> Arc<dyn async_graphql::extensions::Extension> {
+        Arc::new(RelationshipsExtensionImpl {
+            resolver: self.resolver.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "graphql")]
+struct RelationshipsExtensionImpl {
+    resolver: Arc<dyn Relationship>,
+}
+
+#[cfg(feature = "graphql")]
+#[async_trait]
+impl async_graphql::extensions::Extension for RelationshipsExtensionImpl {
+    async fn prepare_request(
+        &self,
+        ctx: &async_graphql::extensions::ExtensionContext<'_>,
+        request: async_graphql::Request,
+        next: async_graphql::extensions::NextPrepareRequest<'_>,
+    ) -> async_graphql::ServerResult<async_graphql::Request> {
+        let request = request.data(Relationships::new(self.resolver.clone()));
+        next.run(ctx, request).await
+    }
+}
+
+/// Insert [`Relationships`] into the extensions of every request to the wrapped service
+///
+/// Handlers can then extract them with `Extension<Relationships>`.
+#[cfg(feature = "axum")]
+#[derive(Clone)]
+pub struct RelationshipsLayer {
+    resolver: Arc<dyn Relationship>,
+}
+
+#[cfg(feature = "axum")]
+impl RelationshipsLayer {
+    /// Create a new layer looking up relationships through the resolver
+    pub fn new<R>(resolver: R) -> Self
+    where
+        R: Relationship,
+    {
+        Self {
+            resolver: Arc::new(resolver),
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+impl<S> tower_layer::Layer<S> for RelationshipsLayer {
+    type Service = RelationshipsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RelationshipsService {
+            inner,
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+/// The service produced by a [`RelationshipsLayer`]
+#[cfg(feature = "axum")]
+#[derive(Clone)]
+pub struct RelationshipsService<S> {
+    inner: S,
+    resolver: Arc<dyn Relationship>,
+}
+
+#[cfg(feature = "axum")]
+impl<S, B> tower_service::Service<http::Request<B>> for RelationshipsService<S>
+where
+    S: tower_service::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        request
+            .extensions_mut()
+            .insert(Relationships::new(self.resolver.clone()));
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemoryRelationships, Relationship, RelationshipError, Relationships, Resource};
+    use crate::UserId;
+    use async_trait::async_trait;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Waits before resolving so concurrent lookups overlap
+    #[derive(Default)]
+    struct SlowRelationships {
+        inner: InMemoryRelationships,
+    }
+
+    #[async_trait]
+    impl Relationship for SlowRelationships {
+        async fn has_relation(
+            &self,
+            user: UserId,
+            relation: &str,
+            resource: &Resource,
+        ) -> Result<bool, RelationshipError> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.inner.has_relation(user, relation, resource).await
+        }
+    }
+
+    #[derive(Default)]
+    pub(super) struct FailingRelationships {
+        attempts: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Relationship for FailingRelationships {
+        async fn has_relation(
+            &self,
+            _user: UserId,
+            _relation: &str,
+            _resource: &Resource,
+        ) -> Result<bool, RelationshipError> {
+            self.attempts.fetch_add(1, Ordering::Relaxed);
+            Err(RelationshipError::new("database unavailable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn in_memory_grant_and_revoke() {
+        let resolver = Arc::new(InMemoryRelationships::default());
+        let application = Resource::new("application", 7);
+        resolver.grant(UserId::from(1), "owner", application.clone());
+
+        let relationships = Relationships::new(resolver.clone());
+        assert!(relationships
+            .has_relation(UserId::from(1), "owner", &application)
+            .await
+            .unwrap());
+        assert!(!relationships
+            .has_relation(UserId::from(2), "owner", &application)
+            .await
+            .unwrap());
+        assert!(!relationships
+            .has_relation(UserId::from(1), "reviewer", &application)
+            .await
+            .unwrap());
+
+        resolver.revoke(UserId::from(1), "owner", application.clone());
+        let relationships = Relationships::new(resolver);
+        assert!(!relationships
+            .has_relation(UserId::from(1), "owner", &application)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn memoizes_lookups() {
+        let resolver = Arc::new(InMemoryRelationships::default());
+        let team = Resource::new("team", "waffles");
+        resolver.grant(UserId::from(1), "member", team.clone());
+
+        let relationships = Relationships::new(resolver.clone());
+        for _ in 0..3 {
+            assert!(relationships
+                .clone()
+                .has_relation(UserId::from(1), "member", &team)
+                .await
+                .unwrap());
+        }
+        assert_eq!(resolver.lookups(), 1);
+
+        Relationships::new(resolver.clone())
+            .has_relation(UserId::from(1), "member", &team)
+            .await
+            .unwrap();
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_resolve_once() {
+        let resolver = Arc::new(SlowRelationships::default());
+        let team = Resource::new("team", "waffles");
+        resolver
+            .inner
+            .grant(UserId::from(1), "member", team.clone());
+
+        let relationships = Relationships::new(resolver.clone());
+        let lookup = || relationships.has_relation(UserId::from(1), "member", &team);
+        let (a, b, c) = tokio::join!(lookup(), lookup(), lookup());
+        assert!(a.unwrap() && b.unwrap() && c.unwrap());
+        assert_eq!(resolver.inner.lookups(), 1);
+    }
+
+    #[tokio::test]
+    async fn failures_are_retried() {
+        let resolver = Arc::new(FailingRelationships::default());
+        let team = Resource::new("team", "waffles");
+
+        let relationships = Relationships::new(resolver.clone());
+        for _ in 0..2 {
+            assert!(relationships
+                .has_relation(UserId::from(1), "member", &team)
+                .await
+                .is_err());
+        }
+        assert_eq!(resolver.attempts.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn resource_display() {
+        assert_eq!(Resource::new("team", "waffles").to_string(), "team:waffles");
+    }
+}
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql_tests {
+    use super::{
+        tests::FailingRelationships, InMemoryRelationships, RelationshipError,
+        RelationshipsExtension, Resource,
+    };
+    use crate::{
+        audit::{Audit, InMemorySink},
+        checks::{has_relationship, RelationshipGuard},
+        Decision, Scope, User, UserId, UserRole,
+    };
+    use async_graphql::{
+        Context, EmptyMutation, EmptySubscription, Object, Request, Result, Schema, Value,
+    };
+    use std::sync::Arc;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn application(&self, ctx: &Context<'_>, id: i32) -> Result<i32> {
+            has_relationship(ctx, "owner", &Resource::new("application", id)).await?;
+            Ok(id)
+        }
+
+        #[graphql(guard = "RelationshipGuard::new(\"owner\", Resource::new(\"application\", id))")]
+        async fn guarded_application(&self, id: i32) -> i32 {
+            id
+        }
+    }
+
+    fn user(id: i32) -> User {
+        User::authenticated()
+            .id(id)
+            .role(UserRole::Participant)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn checks_relationship_once_per_request() {
+        let resolver = Arc::new(InMemoryRelationships::default());
+        resolver.grant(UserId::from(1), "owner", Resource::new("application", 7));
+
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(RelationshipsExtension::new(resolver.clone()))
+            .finish();
+        let query = "{ a: application(id: 7) b: application(id: 7) }";
+
+        let response = schema
+            .execute(Request::new(query).data(Scope::User).data(user(1)))
+            .await;
+        assert!(response.errors.is_empty());
+        assert_eq!(resolver.lookups(), 1);
+
+        let response = schema
+            .execute(Request::new(query).data(Scope::User).data(user(2)))
+            .await;
+        assert_eq!(response.errors.len(), 2);
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn guard_checks_relationship() {
+        let resolver = Arc::new(InMemoryRelationships::default());
+        resolver.grant(UserId::from(1), "owner", Resource::new("application", 7));
+
+        let sink = Arc::new(InMemorySink::default());
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(RelationshipsExtension::new(resolver.clone()))
+            .extension(Audit::new(sink.clone()))
+            .finish();
+        let query = "{ a: guardedApplication(id: 7) b: guardedApplication(id: 8) }";
+
+        let response = schema
+            .execute(Request::new(query).data(Scope::User).data(user(1)))
+            .await;
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(
+            response.errors[0].path,
+            [async_graphql::PathSegment::Field(String::from("b"))]
+        );
+        assert_eq!(resolver.lookups(), 2);
+
+        let allowed = sink
+            .records()
+            .into_iter()
+            .map(|record| (record.path, record.allowed))
+            .collect::<Vec<_>>();
+        assert!(allowed.contains(&(String::from("a"), true)));
+        assert!(allowed.contains(&(String::from("b"), false)));
+        let sink = Arc::new(InMemorySink::default());
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(RelationshipsExtension::new(resolver.clone()))
+            .extension(Audit::new(sink.clone()))
+            .finish();
+        let response = schema
+            .execute(
+                Request::new("{ guardedApplication(id: 7) }")
+                    .data(Scope::User)
+                    .data(User::Unauthenticated),
+            )
+            .await;
+        assert_eq!(response.errors.len(), 1);
+
+        let records = sink.records();
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].allowed);
+    }
+
+    #[tokio::test]
+    async fn denied_without_relationships() {
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription).finish();
+
+        let request = Request::new("{ application(id: 7) }")
+            .data(Scope::User)
+            .data(user(1));
+        assert_eq!(schema.execute(request).await.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_lookup_is_an_internal_error() {
+        let sink = Arc::new(InMemorySink::default());
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(RelationshipsExtension::new(FailingRelationships::default()))
+            .extension(Audit::new(sink.clone()))
+            .finish();
+
+        let request = Request::new("{ application(id: 7) }")
+            .data(Scope::User)
+            .data(user(1));
+        let mut response = schema.execute(request).await;
+        assert_eq!(response.errors.len(), 1);
+
+        let error = response.errors.remove(0);
+        assert!(error.source::<RelationshipError>().is_some());
+        assert!(error.source::<Decision>().is_none());
+        assert_eq!(
+            error.extensions.unwrap().get("code"),
+            Some(&Value::from("INTERNAL_SERVER_ERROR"))
+        );
+
+        let records = sink.records();
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].allowed);
+    }
+}
+
+#[cfg(all(test, feature = "axum"))]
+mod axum_tests {
+    use super::{
+        tests::FailingRelationships, InMemoryRelationships, Relationships, RelationshipsLayer,
+        Resource,
+    };
+    use crate::{
+        axum_checks::{has_relationship, RelationshipRejection},
+        User, UserId,
+    };
+    use axum::{body::Body, routing::get, Extension, Router};
+    use http::{Request, StatusCode};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn handler(
+        user: User,
+        Extension(relationships): Extension<Relationships>,
+    ) -> Result<&'static str, RelationshipRejection> {
+        let application = Resource::new("application", 7);
+        has_relationship(&user, &relationships, "owner", &application).await?;
+        has_relationship(&user, &relationships, "owner", &application).await?;
+        Ok("ok")
+    }
+
+    fn request(id: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/")
+            .header("User-Session", "authenticated")
+            .header("User-ID", id)
+            .header("User-Given-Name", "John")
+            .header("User-Family-Name", "Doe")
+            .header("User-Email", "john.doe@gmail.com")
+            .header("User-Is-Admin", "false")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn checks_relationship_once_per_request() {
+        let resolver = Arc::new(InMemoryRelationships::default());
+        resolver.grant(UserId::from(1), "owner", Resource::new("application", 7));
+
+        let router = Router::new()
+            .route("/", get(handler))
+            .layer(RelationshipsLayer::new(resolver.clone()));
+
+        let response = router.clone().oneshot(request("1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(resolver.lookups(), 1);
+
+        let response = router.oneshot(request("2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_lookup_is_an_internal_error() {
+        let router = Router::new()
+            .route("/", get(handler))
+            .layer(RelationshipsLayer::new(FailingRelationships::default()));
+
+        let response = router.oneshot(request("1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
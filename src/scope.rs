@@ -1,5 +1,13 @@
+#[cfg(feature = "axum")]
+use crate::headers::WithConfig;
 #[cfg(feature = "headers")]
-use crate::headers::{extract, EventOrganizationId, EventSlug, RequestScope};
+use crate::headers::{
+    extract, extract_opt, extract_or, extract_with, resolve_unknown, try_insert, validate,
+    write_atomically, write_with, written_by_newer, Config, ContextVersion, EncodeError,
+    EventDomain, EventOrganizationId, EventSlug, OrganizationId as OrganizationIdHeader,
+    RequestScope, PROTOCOL_VERSION, SCOPE_HEADERS,
+};
+use crate::{id::OrganizationId, validated::Slug};
 #[cfg(feature = "axum")]
 use axum_core::{
     extract::FromRequestParts,
@@ -20,7 +28,7 @@ use std::{borrow::Cow, fmt::Formatter, marker::PhantomData};
 
 /// Query parameters for fetching the scope
 #[derive(Debug)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
 pub enum ScopeParams<'p> {
     /// Find event context for a domain
     Domain(Cow<'p, str>),
@@ -84,7 +92,7 @@ impl<'p> Serialize for ScopeParams<'p> {
 
 /// Information about the scope of the current request
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
 #[serde(rename_all = "lowercase", tag = "kind")]
 pub enum Scope {
     /// A request with global scope
@@ -93,48 +101,138 @@ pub enum Scope {
     User,
     /// A request scoped to an event
     Event(EventScope),
+    /// A request scoped to an organization (i.e. billing, members, cross-event reports)
+    Organization(OrganizationScope),
+}
+
+impl Scope {
+    /// The ID of the organization the request applies to, if any
+    pub fn organization_id(&self) -> Option<OrganizationId> {
+        match self {
+            Scope::Admin | Scope::User => None,
+            Scope::Event(context) => Some(context.organization_id),
+            Scope::Organization(context) => Some(context.organization_id),
+        }
+    }
 }
 
 #[cfg(feature = "headers")]
 impl Scope {
     /// Serialize the context into request headers
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value cannot be represented as a header, see [`Scope::try_into_headers`].
     pub fn into_headers(self) -> HeaderMap {
+        self.try_into_headers()
+            .expect("scope must be representable as headers")
+    }
+
+    /// Serialize the context into request headers, failing if any value cannot be represented
+    pub fn try_into_headers(self) -> Result<HeaderMap, EncodeError> {
+        self.try_into_headers_with(&Config::default())
+    }
+
+    /// Serialize the context into request headers using the provided configuration
+    pub fn try_into_headers_with(self, config: &Config) -> Result<HeaderMap, EncodeError> {
         let mut map = HeaderMap::with_capacity(1);
-        self.write_headers(&mut map);
-        map
+        self.try_write_headers_with(&mut map, config)?;
+        Ok(map)
     }
 
     /// Write the context to request headers
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value cannot be represented as a header, see [`Scope::try_write_headers`].
     pub fn write_headers(self, headers: &mut HeaderMap) {
-        match self {
-            Scope::Admin => headers.typed_insert(RequestScope::Admin),
-            Scope::User => headers.typed_insert(RequestScope::User),
-            Scope::Event(context) => {
-                headers.typed_insert(RequestScope::Event);
-                context.write_headers(headers);
+        self.try_write_headers(headers)
+            .expect("scope must be representable as headers")
+    }
+
+    /// Write the context to request headers, failing if any value cannot be represented
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        self.try_write_headers_with(headers, &Config::default())
+    }
+
+    /// Write the context to request headers using the provided configuration
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn try_write_headers_with(
+        self,
+        headers: &mut HeaderMap,
+        config: &Config,
+    ) -> Result<(), EncodeError> {
+        write_with(headers, config, |headers| {
+            headers.typed_insert(ContextVersion::from(PROTOCOL_VERSION));
+            match self {
+                Scope::Admin => headers.typed_insert(RequestScope::Admin),
+                Scope::User => headers.typed_insert(RequestScope::User),
+                Scope::Event(context) => {
+                    headers.typed_insert(RequestScope::Event);
+                    context.try_write_headers(headers)?;
+                }
+                Scope::Organization(context) => {
+                    headers.typed_insert(RequestScope::Organization);
+                    context.try_write_headers(headers)?;
+                }
             }
-        }
+
+            Ok(())
+        })
     }
 }
 
 #[cfg(feature = "headers")]
-impl TryFrom<&HeaderMap> for Scope {
-    type Error = crate::Error;
+impl Scope {
+    /// Extract the context from request headers using the provided configuration
+    ///
+    /// The [`TryFrom`] implementation is equivalent to a non-strict configuration without a
+    /// prefix.
+    pub fn from_headers(headers: &HeaderMap, config: &Config) -> Result<Self, crate::Error> {
+        extract_with(headers, config, &SCOPE_HEADERS, |headers| {
+            Self::decode(headers, config.keeps_unknown_values())
+        })
+    }
 
-    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
-        let scope = extract::<RequestScope>(headers)?;
+    /// Extract the context from request headers, tolerating unknown scopes if `keep_unknown` is
+    /// set
+    fn decode(headers: &HeaderMap, keep_unknown: bool) -> Result<Self, crate::Error> {
+        let newer = written_by_newer(headers)?;
+        let scope = extract_or(headers, newer, || RequestScope::User)?;
 
         Ok(match scope {
+            // an unknown scope could restrict access in ways we cannot enforce, so fall back to
+            // the most restrictive scope
+            RequestScope::Unknown(_) => {
+                resolve_unknown(scope, keep_unknown, newer)?;
+                Self::User
+            }
             RequestScope::Admin => Self::Admin,
             RequestScope::User => Self::User,
             RequestScope::Event => {
                 let context = EventScope::try_from(headers)?;
                 Self::Event(context)
             }
+            RequestScope::Organization => {
+                let context = OrganizationScope::try_from(headers)?;
+                Self::Organization(context)
+            }
         })
     }
 }
 
+#[cfg(feature = "headers")]
+impl TryFrom<&HeaderMap> for Scope {
+    type Error = crate::Error;
+
+    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
+        Self::decode(headers, false)
+    }
+}
+
 #[cfg(feature = "axum")]
 #[async_trait::async_trait]
 impl<S> FromRequestParts<S> for Scope
@@ -144,43 +242,90 @@ where
     type Rejection = crate::Error;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        Self::try_from(&parts.headers)
+        match parts.extensions.get::<Config>() {
+            Some(config) => Self::from_headers(&parts.headers, config),
+            None => Self::from_headers(&parts.headers, &Config::default()),
+        }
     }
 }
 
 #[cfg(feature = "axum")]
 impl IntoResponseParts for Scope {
-    type Error = std::convert::Infallible;
+    type Error = EncodeError;
+
+    fn into_response_parts(self, res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        WithConfig(self, &Config::default()).into_response_parts(res)
+    }
+}
+
+#[cfg(feature = "axum")]
+impl IntoResponse for Scope {
+    fn into_response(self) -> Response {
+        WithConfig(self, &Config::default()).into_response()
+    }
+}
+
+#[cfg(feature = "axum")]
+impl IntoResponseParts for WithConfig<'_, Scope> {
+    type Error = EncodeError;
 
     fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
-        self.write_headers(res.headers_mut());
+        let WithConfig(scope, config) = self;
+        scope.try_write_headers_with(res.headers_mut(), config)?;
         Ok(res)
     }
 }
 
 #[cfg(feature = "axum")]
-impl IntoResponse for Scope {
+impl IntoResponse for WithConfig<'_, Scope> {
     fn into_response(self) -> Response {
-        self.into_headers().into_response()
+        let WithConfig(scope, config) = self;
+        match scope.try_into_headers_with(config) {
+            Ok(headers) => headers.into_response(),
+            Err(e) => e.into_response(),
+        }
     }
 }
 
 /// Additional information about a request scoped to an event
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct EventScope {
     /// The event slug
-    pub event: String,
+    pub event: Slug,
     /// The ID of the organization that manages the event
-    pub organization_id: i32,
+    pub organization_id: OrganizationId,
+    /// The custom domain the event is served from, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
 }
 
 #[cfg(feature = "headers")]
 impl EventScope {
     /// Write the context to request headers
+    ///
+    /// # Panics
+    ///
+    /// Panics if the domain is not ASCII, see [`EventScope::try_write_headers`].
     pub fn write_headers(self, headers: &mut HeaderMap) {
-        headers.typed_insert(EventSlug::from(self.event));
-        headers.typed_insert(EventOrganizationId::from(self.organization_id));
+        self.try_write_headers(headers)
+            .expect("event scope must be representable as headers")
+    }
+
+    /// Write the context to request headers, failing if any value cannot be represented
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        write_atomically(headers, |headers| {
+            try_insert(headers, EventSlug::from(self.event.into_inner()))?;
+            headers.typed_insert(EventOrganizationId::from(self.organization_id.get()));
+            if let Some(domain) = self.domain {
+                try_insert(headers, EventDomain::from(domain))?;
+            }
+
+            Ok(())
+        })
     }
 }
 
@@ -189,19 +334,60 @@ impl TryFrom<&HeaderMap> for EventScope {
     type Error = crate::Error;
 
     fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
-        let event = extract::<EventSlug>(headers)?;
+        let event = validate(extract::<EventSlug>(headers)?)?;
         let organization_id = extract::<EventOrganizationId>(headers)?;
+        let domain = extract_opt::<EventDomain>(headers)?;
 
         Ok(Self {
-            event: event.into_inner(),
-            organization_id: organization_id.into_inner(),
+            event,
+            organization_id: organization_id.into_inner().into(),
+            domain: domain.map(EventDomain::into_inner),
+        })
+    }
+}
+
+/// Additional information about a request scoped to an organization
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct OrganizationScope {
+    /// The ID of the organization
+    pub organization_id: OrganizationId,
+}
+
+#[cfg(feature = "headers")]
+impl OrganizationScope {
+    /// Write the context to request headers
+    pub fn write_headers(self, headers: &mut HeaderMap) {
+        headers.typed_insert(OrganizationIdHeader::from(self.organization_id.get()));
+    }
+
+    /// Write the context to request headers
+    ///
+    /// Organization scopes can always be represented, this exists for parity with
+    /// [`EventScope::try_write_headers`].
+    pub fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        self.write_headers(headers);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "headers")]
+impl TryFrom<&HeaderMap> for OrganizationScope {
+    type Error = crate::Error;
+
+    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
+        let organization_id = extract::<OrganizationIdHeader>(headers)?;
+
+        Ok(Self {
+            organization_id: organization_id.into_inner().into(),
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{EventScope, Scope, ScopeParams};
+    use super::{EventScope, OrganizationId, OrganizationScope, Scope, ScopeParams, Slug};
     use std::borrow::Cow;
 
     #[test]
@@ -239,8 +425,9 @@ mod tests {
     #[test]
     fn context_event_serializes_as_tagged_union() {
         let ctx = Scope::Event(EventScope {
-            event: String::from("testing"),
-            organization_id: 45,
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(45),
+            domain: None,
         });
         let serialized = serde_json::to_string(&ctx).unwrap();
         assert_eq!(
@@ -248,12 +435,59 @@ mod tests {
             r#"{"kind":"event","event":"testing","organization_id":45}"#
         );
     }
+
+    #[test]
+    fn context_event_with_domain_serializes_as_tagged_union() {
+        let ctx = Scope::Event(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(45),
+            domain: Some(String::from("testing.events")),
+        });
+        let serialized = serde_json::to_string(&ctx).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"kind":"event","event":"testing","organization_id":45,"domain":"testing.events"}"#
+        );
+
+        let deserialized: Scope = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, ctx);
+    }
+
+    #[test]
+    fn context_event_without_domain_deserializes() {
+        let deserialized: Scope =
+            serde_json::from_str(r#"{"kind":"event","event":"testing","organization_id":45}"#)
+                .unwrap();
+        let Scope::Event(context) = deserialized else {
+            panic!("expected Context::Event, got {deserialized:?}")
+        };
+        assert_eq!(context.domain, None);
+    }
+
+    #[test]
+    fn context_organization_serializes_as_tagged_union() {
+        let ctx = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(45),
+        });
+        let serialized = serde_json::to_string(&ctx).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"kind":"organization","organization_id":45}"#
+        );
+
+        let deserialized: Scope = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, ctx);
+    }
 }
 
 #[cfg(all(test, feature = "headers"))]
 mod headers_tests {
-    use super::{EventScope, Scope};
-    use crate::{error_test_cases, headers, headers::ErrorKind};
+    use super::{EventScope, OrganizationId, OrganizationScope, Scope, Slug};
+    use crate::{
+        error_test_cases, headers,
+        headers::{Config, ErrorKind, PROTOCOL_VERSION},
+        ValidationError,
+    };
 
     error_test_cases! {
         for Scope;
@@ -297,7 +531,41 @@ mod headers_tests {
         };
 
         assert_eq!(context.event, "wafflehacks");
-        assert_eq!(context.organization_id, 5);
+        assert_eq!(context.organization_id, OrganizationId::from(5));
+        assert_eq!(context.domain, None);
+    }
+
+    #[tokio::test]
+    async fn try_from_organization_64_bit_id() {
+        let headers = headers! {
+            "Request-Scope" => "organization",
+            "Organization-ID" => "3000000000",
+        };
+
+        #[cfg(feature = "bigint")]
+        assert_eq!(
+            Scope::try_from(&headers).unwrap().organization_id(),
+            Some(OrganizationId::new(3_000_000_000))
+        );
+        #[cfg(not(feature = "bigint"))]
+        assert!(Scope::try_from(&headers).is_err());
+    }
+
+    #[tokio::test]
+    async fn try_from_event_with_domain_valid() {
+        let headers = headers! {
+            "Request-Scope" => "event",
+            "Event-Slug" => "wafflehacks",
+            "Event-Organization-ID" => "5",
+            "Event-Domain" => "wafflehacks.org",
+        };
+
+        let context = Scope::try_from(&headers).unwrap();
+        let Scope::Event(context) = context else {
+            panic!("expected Context::Event, got {context:?}")
+        };
+
+        assert_eq!(context.domain.as_deref(), Some("wafflehacks.org"));
     }
 
     error_test_cases! {
@@ -336,6 +604,45 @@ mod headers_tests {
             header: "event-organization-id",
             kind: ErrorKind::Error(_),
         };
+        try_from_event_invalid_slug(
+            "Request-Scope" => "event",
+            "Event-Slug" => "Waffle Hacks",
+            "Event-Organization-ID" => "5",
+        ) => {
+            header: "event-slug",
+            kind: ErrorKind::Invalid(ValidationError::InvalidCharacter('W')),
+        };
+    }
+
+    #[tokio::test]
+    async fn try_from_organization_valid() {
+        let headers = headers! {
+            "Request-Scope" => "organization",
+            "Organization-ID" => "5",
+        };
+
+        let context = Scope::try_from(&headers).unwrap();
+        assert_eq!(
+            context,
+            Scope::Organization(OrganizationScope {
+                organization_id: OrganizationId::from(5)
+            })
+        );
+    }
+
+    error_test_cases! {
+        for Scope;
+        try_from_organization_missing_id("Request-Scope" => "organization") => {
+            header: "organization-id",
+            kind: ErrorKind::Missing,
+        };
+        try_from_organization_invalid_id(
+            "Request-Scope" => "organization",
+            "Organization-ID" => "af",
+        ) => {
+            header: "organization-id",
+            kind: ErrorKind::Error(_),
+        };
     }
 
     #[test]
@@ -353,14 +660,39 @@ mod headers_tests {
     #[test]
     fn event_into_headers() {
         let context = Scope::Event(EventScope {
-            event: String::from("testing"),
-            organization_id: 99,
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: None,
         });
         let headers = context.into_headers();
 
         assert_eq!(headers.get("request-scope").unwrap(), "event");
         assert_eq!(headers.get("event-slug").unwrap(), "testing");
         assert_eq!(headers.get("event-organization-id").unwrap(), "99");
+        assert!(headers.get("event-domain").is_none());
+    }
+
+    #[test]
+    fn event_with_domain_into_headers() {
+        let context = Scope::Event(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: Some(String::from("testing.events")),
+        });
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("event-domain").unwrap(), "testing.events");
+    }
+
+    #[test]
+    fn organization_into_headers() {
+        let context = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(99),
+        });
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("request-scope").unwrap(), "organization");
+        assert_eq!(headers.get("organization-id").unwrap(), "99");
     }
 
     #[tokio::test]
@@ -384,20 +716,225 @@ mod headers_tests {
     #[tokio::test]
     async fn round_trip_event_context() {
         let context = Scope::Event(EventScope {
-            event: String::from("testing"),
-            organization_id: 99,
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: None,
+        });
+
+        let headers = context.clone().into_headers();
+        let roundtripped = Scope::try_from(&headers).unwrap();
+        assert_eq!(context, roundtripped);
+    }
+
+    #[tokio::test]
+    async fn round_trip_event_with_domain_context() {
+        let context = Scope::Event(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: Some(String::from("testing.events")),
         });
 
         let headers = context.clone().into_headers();
         let roundtripped = Scope::try_from(&headers).unwrap();
         assert_eq!(context, roundtripped);
     }
+
+    #[tokio::test]
+    async fn round_trip_organization_context() {
+        let context = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(99),
+        });
+
+        let headers = context.clone().into_headers();
+        let roundtripped = Scope::try_from(&headers).unwrap();
+        assert_eq!(context, roundtripped);
+    }
+
+    #[test]
+    fn try_write_headers_is_atomic() {
+        let mut headers = headers! {
+            "Request-Scope" => "user",
+        };
+
+        let context = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: Some(String::from("wäfflehacks.events")),
+        });
+        let err = context.try_write_headers(&mut headers).unwrap_err();
+
+        assert_eq!(err.name.as_str(), "event-domain");
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("request-scope").unwrap(), "user");
+    }
+
+    #[test]
+    #[should_panic(expected = "scope must be representable as headers")]
+    fn into_headers_panics_on_unrepresentable_value() {
+        let context = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: Some(String::from("wafflehacks\r\n.events")),
+        });
+        context.into_headers();
+    }
+
+    #[test]
+    fn try_into_headers_arbitrary_strings() {
+        for value in crate::test_util::arbitrary_strings() {
+            let context = Scope::Event(EventScope {
+                event: Slug::new("wafflehacks").unwrap(),
+                organization_id: OrganizationId::from(5),
+                domain: Some(value.clone()),
+            });
+
+            let representable = value
+                .bytes()
+                .all(|b| b == b'\t' || (b' '..=b'~').contains(&b));
+            match context.clone().try_into_headers() {
+                Ok(headers) => {
+                    assert!(representable, "{value:?}");
+                    let roundtripped = Scope::try_from(&headers).unwrap();
+                    assert_eq!(context, roundtripped, "{value:?}");
+                }
+                Err(e) => {
+                    assert!(!representable, "{value:?}");
+                    assert_eq!(e.name.as_str(), "event-domain", "{value:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_headers_strict_rejects_duplicates() {
+        let mut headers = headers! {
+            "Request-Scope" => "event",
+            "Event-Slug" => "wafflehacks",
+            "Event-Organization-ID" => "5",
+        };
+        headers.append("Event-Slug", "evil".parse().unwrap());
+
+        let err = Scope::from_headers(&headers, &Config::default()).unwrap_err();
+        assert_eq!(err.name.as_str(), "event-slug");
+        assert!(matches!(err.kind, ErrorKind::Duplicated));
+
+        let context = Scope::from_headers(&headers, &Config::new().strict(false)).unwrap();
+        assert_eq!(context, Scope::try_from(&headers).unwrap());
+    }
+
+    #[test]
+    fn prefixed_roundtrip() {
+        let config = Config::new().prefix("X-THA-").unwrap();
+        let context = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: Some(String::from("wafflehacks.events")),
+        });
+
+        let headers = context.clone().try_into_headers_with(&config).unwrap();
+        assert_eq!(headers.get("x-tha-request-scope").unwrap(), "event");
+        assert_eq!(headers.get("x-tha-event-slug").unwrap(), "wafflehacks");
+        assert!(headers.get("request-scope").is_none());
+        assert!(Scope::try_from(&headers).is_err());
+
+        let roundtripped = Scope::from_headers(&headers, &config).unwrap();
+        assert_eq!(context, roundtripped);
+    }
+
+    #[test]
+    fn prefixed_ignores_unprefixed_headers() {
+        let config = Config::new().prefix("x-tha-").unwrap();
+        let headers = headers! {
+            "Request-Scope" => "admin",
+            "X-THA-Request-Scope" => "organization",
+            "X-THA-Organization-ID" => "7",
+        };
+
+        let context = Scope::from_headers(&headers, &config).unwrap();
+        assert_eq!(
+            context,
+            Scope::Organization(OrganizationScope {
+                organization_id: OrganizationId::from(7)
+            })
+        );
+    }
+
+    #[test]
+    fn prefixed_errors_use_prefixed_name() {
+        let config = Config::new().prefix("X-THA-").unwrap();
+        let headers = headers! {
+            "X-THA-Request-Scope" => "organization",
+        };
+
+        let err = Scope::from_headers(&headers, &config).unwrap_err();
+        assert_eq!(err.name.as_str(), "x-tha-organization-id");
+        assert!(matches!(err.kind, ErrorKind::Missing));
+    }
+
+    #[test]
+    fn invalid_prefix() {
+        assert!(Config::new().prefix("X THA ").is_err());
+        assert_eq!(Config::new().prefix("").unwrap().header_prefix(), None);
+    }
+
+    #[test]
+    fn new_writer_old_reader_unknown_scope() {
+        let headers = headers! {
+            "Context-Version" => (PROTOCOL_VERSION + 1).to_string(),
+            "Request-Scope" => "team",
+            "Team-ID" => "12",
+        };
+
+        let context = Scope::try_from(&headers).unwrap();
+        assert_eq!(context, Scope::User);
+    }
+
+    #[test]
+    fn unknown_scope_is_user_when_opted_in() {
+        let headers = headers! {
+            "Request-Scope" => "team",
+        };
+
+        let config = Config::new().unknown_values(true);
+        let context = Scope::from_headers(&headers, &config).unwrap();
+        assert_eq!(context, Scope::User);
+
+        assert!(Scope::from_headers(&headers, &Config::new()).is_err());
+    }
+
+    #[test]
+    fn unknown_scope_same_version() {
+        let headers = headers! {
+            "Context-Version" => PROTOCOL_VERSION.to_string(),
+            "Request-Scope" => "team",
+        };
+
+        let err = Scope::try_from(&headers).unwrap_err();
+        assert_eq!(err.name.as_str(), "request-scope");
+        assert!(matches!(err.kind, ErrorKind::Error(_)));
+    }
+
+    #[test]
+    fn prefixed_version_is_honoured() {
+        let config = Config::new().prefix("X-THA-").unwrap();
+        let headers = headers! {
+            "X-THA-Context-Version" => (PROTOCOL_VERSION + 1).to_string(),
+            "X-THA-Request-Scope" => "team",
+        };
+
+        let context = Scope::from_headers(&headers, &config).unwrap();
+        assert_eq!(context, Scope::User);
+    }
 }
 
 #[cfg(all(test, feature = "axum"))]
 mod axum_tests {
-    use super::ScopeParams;
-    use axum::extract::{FromRequestParts, Query};
+    use super::{EventScope, OrganizationId, Scope, ScopeParams, Slug};
+    use crate::headers::{Config, ErrorKind, WithConfig};
+    use axum::{
+        extract::{FromRequestParts, Query},
+        response::IntoResponse,
+    };
     use std::borrow::Cow;
 
     #[tokio::test]
@@ -430,4 +967,131 @@ mod axum_tests {
             .unwrap();
         assert_eq!(params, ScopeParams::Slug(Cow::Borrowed("wafflehacks-2023")));
     }
+
+    #[tokio::test]
+    async fn scope_from_request_rejects_duplicates_by_default() {
+        let request = http::request::Request::builder()
+            .header("Request-Scope", "user")
+            .header("Request-Scope", "admin")
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        let err = Scope::from_request_parts(&mut parts, &())
+            .await
+            .unwrap_err();
+        assert_eq!(err.name.as_str(), "request-scope");
+        assert!(matches!(err.kind, ErrorKind::Duplicated));
+    }
+
+    #[tokio::test]
+    async fn scope_from_request_lenient() {
+        let request = http::request::Request::builder()
+            .header("Request-Scope", "user")
+            .header("Request-Scope", "admin")
+            .extension(Config::new().strict(false))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        let scope = Scope::from_request_parts(&mut parts, &()).await.unwrap();
+        assert_eq!(scope, Scope::User);
+    }
+
+    #[tokio::test]
+    async fn prefixed_response_round_trip() {
+        let config = Config::new().prefix("X-THA-").unwrap();
+        let scope = Scope::Event(EventScope {
+            event: Slug::new("wafflehacks").unwrap(),
+            organization_id: OrganizationId::from(1),
+            domain: None,
+        });
+
+        for response in [
+            WithConfig(scope.clone(), &config).into_response(),
+            (WithConfig(scope.clone(), &config), "ok").into_response(),
+        ] {
+            assert!(response.headers().get("request-scope").is_none());
+            assert_eq!(
+                response.headers().get("x-tha-request-scope").unwrap(),
+                "event"
+            );
+
+            let mut request = http::request::Request::builder()
+                .extension(config.clone())
+                .body(())
+                .unwrap();
+            *request.headers_mut() = response.headers().clone();
+            let (mut parts, _) = request.into_parts();
+
+            let extracted = Scope::from_request_parts(&mut parts, &()).await.unwrap();
+            assert_eq!(extracted, scope);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "graphql"))]
+mod graphql_tests {
+    use super::{EventScope, OrganizationId, Slug};
+    use async_graphql::{value, EmptyMutation, EmptySubscription, Object, Schema};
+
+    struct Query(EventScope);
+
+    #[Object]
+    impl Query {
+        async fn scope(&self) -> &EventScope {
+            &self.0
+        }
+    }
+
+    async fn resolve(scope: EventScope) -> async_graphql::Value {
+        let schema = Schema::new(Query(scope), EmptyMutation, EmptySubscription);
+        let response = schema
+            .execute("{ scope { event organizationId domain } }")
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data
+    }
+
+    #[tokio::test]
+    async fn event_with_domain_resolves() {
+        let data = resolve(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: Some(String::from("testing.events")),
+        })
+        .await;
+
+        assert_eq!(
+            data,
+            value!({
+                "scope": {
+                    "event": "testing",
+                    "organizationId": 99,
+                    "domain": "testing.events",
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn event_without_domain_resolves() {
+        let data = resolve(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(99),
+            domain: None,
+        })
+        .await;
+
+        assert_eq!(
+            data,
+            value!({
+                "scope": {
+                    "event": "testing",
+                    "organizationId": 99,
+                    "domain": null,
+                },
+            })
+        );
+    }
 }
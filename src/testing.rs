@@ -0,0 +1,551 @@
+//! Builders, fixtures, and helpers for testing services that consume the context
+//!
+//! Start from [`User::authenticated`], [`User::service`], [`Scope::event`], or
+//! [`Scope::organization`] and adjust only the fields the test cares about, or begin with one of
+//! the [`fixtures`]:
+//!
+//! ```
+//! use context::{testing::fixtures, User, UserRole};
+//!
+//! let manager = User::authenticated().role(UserRole::Manager).admin().build();
+//! let organizer = fixtures::organizer().id(7).build();
+//! ```
+//!
+//! The context can then be attached to requests with [`headers()`], [`request`], or
+//! [`graphql_request`]. The [`headers!`](crate::headers!) and
+//! [`error_test_cases!`](crate::error_test_cases!) macros are also available.
+
+#[cfg(feature = "headers")]
+use crate::{headers::Config, EncodeError};
+use crate::{
+    id::{OrganizationId, UserId},
+    permission::Permission,
+    scope::{EventScope, OrganizationScope, Scope},
+    user::{
+        Assurance, AssuranceLevel, AuthenticatedUser, EventMembership, Impersonator,
+        ParticipantStatus, RoleWindow, ServiceUser, SessionMetadata, User, UserRole,
+    },
+    validated::{Email, Slug},
+};
+#[cfg(feature = "headers")]
+use http::HeaderMap;
+use std::time::SystemTime;
+
+/// Builds an [`AuthenticatedUser`], starting from John Doe with no role
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUserBuilder(AuthenticatedUser);
+
+impl User {
+    /// Start building an authenticated user
+    pub fn authenticated() -> AuthenticatedUserBuilder {
+        AuthenticatedUserBuilder(AuthenticatedUser {
+            id: UserId::from(1),
+            given_name: String::from("John"),
+            family_name: String::from("Doe"),
+            email: Email::new("john.doe@gmail.com").expect("email must be valid"),
+            role: None,
+            role_window: None,
+            is_admin: false,
+            participant_status: None,
+            memberships: None,
+            impersonator: None,
+            session: None,
+            assurance: None,
+        })
+    }
+
+    /// Start building a service with no permissions
+    pub fn service(name: impl Into<String>) -> ServiceUserBuilder {
+        ServiceUserBuilder(ServiceUser {
+            name: name.into(),
+            permissions: Vec::new(),
+        })
+    }
+}
+
+impl AuthenticatedUserBuilder {
+    /// Set the user's ID
+    pub fn id(mut self, id: impl Into<UserId>) -> Self {
+        self.0.id = id.into();
+        self
+    }
+
+    /// Set the user's given and family names
+    pub fn name(mut self, given_name: &str, family_name: &str) -> Self {
+        self.0.given_name = given_name.to_owned();
+        self.0.family_name = family_name.to_owned();
+        self
+    }
+
+    /// Set the user's email
+    ///
+    /// # Panics
+    ///
+    /// Panics if the email is invalid.
+    pub fn email(mut self, email: &str) -> Self {
+        self.0.email = Email::new(email).expect("email must be valid");
+        self
+    }
+
+    /// Set the user's role for the scope
+    pub fn role(mut self, role: UserRole) -> Self {
+        self.0.role = Some(role);
+        self
+    }
+
+    /// Only grant the role between the times
+    pub fn role_window(mut self, valid_from: SystemTime, valid_until: SystemTime) -> Self {
+        self.0.role_window = Some(RoleWindow {
+            valid_from,
+            valid_until,
+        });
+        self
+    }
+
+    /// Make the user an admin
+    pub fn admin(mut self) -> Self {
+        self.0.is_admin = true;
+        self
+    }
+
+    /// Set where the user is in the participant lifecycle
+    pub fn participant_status(mut self, status: ParticipantStatus) -> Self {
+        self.0.participant_status = Some(status);
+        self
+    }
+
+    /// Add the user's role within another event
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event slug is invalid.
+    pub fn membership(mut self, event: &str, role: UserRole) -> Self {
+        let membership = EventMembership {
+            event: Slug::new(event).expect("event slug must be valid"),
+            role,
+        };
+        self.0
+            .memberships
+            .get_or_insert_with(Vec::new)
+            .push(membership);
+        self
+    }
+
+    /// Have the user be impersonated by another user since the time
+    pub fn impersonated_by(
+        mut self,
+        id: impl Into<UserId>,
+        reason: &str,
+        started_at: SystemTime,
+    ) -> Self {
+        self.0.impersonator = Some(Impersonator {
+            id: id.into(),
+            reason: reason.to_owned(),
+            started_at,
+        });
+        self
+    }
+
+    /// Set the session the request was made with
+    pub fn session(mut self, session: SessionMetadata) -> Self {
+        self.0.session = Some(session);
+        self
+    }
+
+    /// Have the user authenticated at the level at the time
+    pub fn assurance(mut self, level: AssuranceLevel, authenticated_at: SystemTime) -> Self {
+        self.0.assurance = Some(Assurance {
+            level,
+            authenticated_at,
+        });
+        self
+    }
+
+    /// Finish building the user
+    pub fn build(self) -> User {
+        User::Authenticated(self.0)
+    }
+}
+
+impl From<AuthenticatedUserBuilder> for AuthenticatedUser {
+    fn from(builder: AuthenticatedUserBuilder) -> Self {
+        builder.0
+    }
+}
+
+impl From<AuthenticatedUserBuilder> for User {
+    fn from(builder: AuthenticatedUserBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builds a [`ServiceUser`]
+#[derive(Clone, Debug)]
+pub struct ServiceUserBuilder(ServiceUser);
+
+impl ServiceUserBuilder {
+    /// Grant the service a permission
+    pub fn permission(mut self, permission: Permission) -> Self {
+        self.0.permissions.push(permission);
+        self
+    }
+
+    /// Finish building the service
+    pub fn build(self) -> User {
+        User::Service(self.0)
+    }
+}
+
+impl From<ServiceUserBuilder> for ServiceUser {
+    fn from(builder: ServiceUserBuilder) -> Self {
+        builder.0
+    }
+}
+
+impl From<ServiceUserBuilder> for User {
+    fn from(builder: ServiceUserBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builds an [`EventScope`], starting in organization 1 without a custom domain
+#[derive(Clone, Debug)]
+pub struct EventScopeBuilder(EventScope);
+
+impl Scope {
+    /// Start building a scope for the event
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event slug is invalid.
+    pub fn event(event: &str) -> EventScopeBuilder {
+        EventScopeBuilder(EventScope {
+            event: Slug::new(event).expect("event slug must be valid"),
+            organization_id: OrganizationId::from(1),
+            domain: None,
+        })
+    }
+
+    /// Start building a scope for the organization
+    pub fn organization(organization_id: impl Into<OrganizationId>) -> OrganizationScopeBuilder {
+        OrganizationScopeBuilder(OrganizationScope {
+            organization_id: organization_id.into(),
+        })
+    }
+}
+
+impl EventScopeBuilder {
+    /// Set the organization that manages the event
+    pub fn organization(mut self, organization_id: impl Into<OrganizationId>) -> Self {
+        self.0.organization_id = organization_id.into();
+        self
+    }
+
+    /// Serve the event from a custom domain
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.0.domain = Some(domain.to_owned());
+        self
+    }
+
+    /// Finish building the scope
+    pub fn build(self) -> Scope {
+        Scope::Event(self.0)
+    }
+}
+
+impl From<EventScopeBuilder> for EventScope {
+    fn from(builder: EventScopeBuilder) -> Self {
+        builder.0
+    }
+}
+
+impl From<EventScopeBuilder> for Scope {
+    fn from(builder: EventScopeBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Builds an [`OrganizationScope`]
+#[derive(Clone, Debug)]
+pub struct OrganizationScopeBuilder(OrganizationScope);
+
+impl OrganizationScopeBuilder {
+    /// Finish building the scope
+    pub fn build(self) -> Scope {
+        Scope::Organization(self.0)
+    }
+}
+
+impl From<OrganizationScopeBuilder> for OrganizationScope {
+    fn from(builder: OrganizationScopeBuilder) -> Self {
+        builder.0
+    }
+}
+
+impl From<OrganizationScopeBuilder> for Scope {
+    fn from(builder: OrganizationScopeBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Commonly used users and scopes
+pub mod fixtures {
+    use super::{AuthenticatedUserBuilder, EventScopeBuilder, OrganizationScopeBuilder};
+    use crate::{
+        permission::Permission,
+        scope::Scope,
+        user::{ParticipantStatus, User, UserRole},
+    };
+
+    /// An event named `wafflehacks` in organization 1
+    pub fn event() -> EventScopeBuilder {
+        Scope::event("wafflehacks")
+    }
+
+    /// Organization 1
+    pub fn organization() -> OrganizationScopeBuilder {
+        Scope::organization(1)
+    }
+
+    /// A participant who has applied to the event
+    pub fn participant() -> AuthenticatedUserBuilder {
+        User::authenticated()
+            .role(UserRole::Participant)
+            .participant_status(ParticipantStatus::Applied)
+    }
+
+    /// A volunteer for the event
+    pub fn volunteer() -> AuthenticatedUserBuilder {
+        User::authenticated().role(UserRole::Volunteer)
+    }
+
+    /// An organizer of the event
+    pub fn organizer() -> AuthenticatedUserBuilder {
+        User::authenticated().role(UserRole::Organizer)
+    }
+
+    /// A manager of the event or organization
+    pub fn manager() -> AuthenticatedUserBuilder {
+        User::authenticated().role(UserRole::Manager)
+    }
+
+    /// A director of the event or organization
+    pub fn director() -> AuthenticatedUserBuilder {
+        User::authenticated().role(UserRole::Director)
+    }
+
+    /// The owner of the organization
+    pub fn owner() -> AuthenticatedUserBuilder {
+        User::authenticated().role(UserRole::Owner)
+    }
+
+    /// An admin without a role
+    pub fn admin() -> AuthenticatedUserBuilder {
+        User::authenticated().admin()
+    }
+
+    /// A service allowed to read applications
+    pub fn service() -> User {
+        User::service("mailer")
+            .permission(Permission::ReadApplications)
+            .build()
+    }
+}
+
+/// Serialize the context into request headers
+///
+/// # Panics
+///
+/// Panics if any value cannot be represented as a header.
+#[cfg(feature = "headers")]
+pub fn headers(scope: impl Into<Scope>, user: impl Into<User>) -> HeaderMap {
+    headers_with(scope, user, &Config::default()).expect("context must be representable")
+}
+
+/// Serialize the context into request headers using the provided configuration
+#[cfg(feature = "headers")]
+pub fn headers_with(
+    scope: impl Into<Scope>,
+    user: impl Into<User>,
+    config: &Config,
+) -> Result<HeaderMap, EncodeError> {
+    let mut headers = scope.into().try_into_headers_with(config)?;
+    user.into().try_write_headers_with(&mut headers, config)?;
+    Ok(headers)
+}
+
+/// Start building a request carrying the context in its headers
+///
+/// # Panics
+///
+/// Panics if any value cannot be represented as a header.
+#[cfg(feature = "axum")]
+pub fn request(scope: impl Into<Scope>, user: impl Into<User>) -> http::request::Builder {
+    let mut builder = http::Request::builder();
+    if let Some(request) = builder.headers_mut() {
+        request.extend(headers(scope, user));
+    }
+    builder
+}
+
+/// Create a GraphQL request with the context in its data
+#[cfg(feature = "graphql")]
+pub fn graphql_request(
+    request: impl Into<async_graphql::Request>,
+    scope: impl Into<Scope>,
+    user: impl Into<User>,
+) -> async_graphql::Request {
+    request.into().data(scope.into()).data(user.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures;
+    use crate::{
+        time::from_unix_seconds, Assurance, AssuranceLevel, AuthenticatedUser, Email, EventScope,
+        Impersonator, OrganizationId, OrganizationScope, Permission, Scope, ServiceUser, Slug,
+        User, UserId, UserRole,
+    };
+
+    #[test]
+    fn builds_authenticated_user() {
+        let user = User::authenticated()
+            .id(42)
+            .role(UserRole::Manager)
+            .admin()
+            .build();
+
+        assert_eq!(
+            user,
+            User::Authenticated(AuthenticatedUser {
+                id: UserId::from(42),
+                given_name: String::from("John"),
+                family_name: String::from("Doe"),
+                email: Email::new("john.doe@gmail.com").unwrap(),
+                role: Some(UserRole::Manager),
+                role_window: None,
+                is_admin: true,
+                participant_status: None,
+                memberships: None,
+                impersonator: None,
+                session: None,
+                assurance: None,
+            })
+        );
+    }
+
+    #[test]
+    fn builds_service_and_scopes() {
+        assert_eq!(
+            fixtures::service(),
+            User::Service(ServiceUser {
+                name: String::from("mailer"),
+                permissions: vec![Permission::ReadApplications],
+            })
+        );
+        assert_eq!(
+            Scope::event("wafflehacks")
+                .organization(5)
+                .domain("wafflehacks.org")
+                .build(),
+            Scope::Event(EventScope {
+                event: Slug::new("wafflehacks").unwrap(),
+                organization_id: OrganizationId::from(5),
+                domain: Some(String::from("wafflehacks.org")),
+            })
+        );
+        assert_eq!(
+            OrganizationScope::from(Scope::organization(5)),
+            OrganizationScope {
+                organization_id: OrganizationId::from(5),
+            }
+        );
+    }
+
+    #[test]
+    fn builds_with_fixed_times() {
+        let user = AuthenticatedUser::from(
+            User::authenticated()
+                .impersonated_by(7, "debugging", from_unix_seconds(1_700_000_000))
+                .assurance(AssuranceLevel::Mfa, from_unix_seconds(1_700_000_060)),
+        );
+
+        assert_eq!(
+            user.impersonator,
+            Some(Impersonator {
+                id: UserId::from(7),
+                reason: String::from("debugging"),
+                started_at: from_unix_seconds(1_700_000_000),
+            })
+        );
+        assert_eq!(
+            user.assurance,
+            Some(Assurance {
+                level: AssuranceLevel::Mfa,
+                authenticated_at: from_unix_seconds(1_700_000_060),
+            })
+        );
+    }
+
+    #[cfg(feature = "headers")]
+    #[test]
+    fn headers_roundtrip() {
+        use crate::ParticipantStatus;
+
+        let user = fixtures::participant()
+            .membership("hackthenorth", UserRole::Organizer)
+            .build();
+        let headers = super::headers(fixtures::event(), user.clone());
+
+        assert_eq!(Scope::try_from(&headers).unwrap(), fixtures::event().into());
+        assert_eq!(User::try_from(&headers).unwrap(), user);
+
+        let User::Authenticated(user) = user else {
+            unreachable!()
+        };
+        assert_eq!(user.participant_status, Some(ParticipantStatus::Applied));
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn request_carries_context() {
+        use axum::extract::FromRequestParts;
+
+        let request = super::request(fixtures::organization(), fixtures::owner())
+            .uri("/")
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+
+        let scope = Scope::from_request_parts(&mut parts, &()).await.unwrap();
+        assert_eq!(scope, fixtures::organization().build());
+        let user = User::from_request_parts(&mut parts, &()).await.unwrap();
+        assert_eq!(user, fixtures::owner().build());
+    }
+
+    #[cfg(feature = "graphql")]
+    #[tokio::test]
+    async fn graphql_request_carries_context() {
+        use crate::checks::{guard_where, has_at_least_role};
+        use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+        struct Query;
+
+        #[Object]
+        impl Query {
+            #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+            async fn applications(&self) -> i32 {
+                1
+            }
+        }
+
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription).finish();
+        let request =
+            super::graphql_request("{ applications }", fixtures::event(), fixtures::organizer());
+        assert!(schema.execute(request).await.errors.is_empty());
+
+        let request =
+            super::graphql_request("{ applications }", fixtures::event(), fixtures::volunteer());
+        assert_eq!(schema.execute(request).await.errors.len(), 1);
+    }
+}
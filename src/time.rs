@@ -0,0 +1,137 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of the current time
+///
+/// Checks that depend on the time, such as whether a role grant has expired, read it from a clock
+/// so it can be controlled in tests.
+pub trait Clock: Send + Sync {
+    /// Get the current time
+    fn now(&self) -> SystemTime;
+}
+
+/// A [`Clock`] that reads the system time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] that is frozen at a point in time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FixedClock(SystemTime);
+
+impl FixedClock {
+    /// Create a clock that always reads the time
+    pub fn new(time: SystemTime) -> Self {
+        Self(time)
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// Convert a timestamp into the number of seconds since the Unix epoch
+///
+/// Times before the epoch are clamped to the epoch.
+pub(crate) fn to_unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Convert a number of seconds since the Unix epoch into a timestamp
+///
+/// Returns `None` if the timestamp cannot be represented on this platform.
+pub(crate) fn try_from_unix_seconds(seconds: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Convert a number of seconds since the Unix epoch into a timestamp
+///
+/// # Panics
+///
+/// Panics if the timestamp cannot be represented on this platform.
+#[cfg(test)]
+pub(crate) fn from_unix_seconds(seconds: u64) -> SystemTime {
+    try_from_unix_seconds(seconds).expect("timestamp must be representable")
+}
+
+/// (De)serialize a [`SystemTime`] as the number of seconds since the Unix epoch
+pub(crate) mod unix_seconds {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(super::to_unix_seconds(*time))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let seconds = u64::deserialize(deserializer)?;
+        super::try_from_unix_seconds(seconds)
+            .ok_or_else(|| D::Error::custom("timestamp out of range"))
+    }
+}
+
+/// (De)serialize an optional [`SystemTime`] as the number of seconds since the Unix epoch
+#[cfg(feature = "policy")]
+pub(crate) mod option_unix_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match time {
+            Some(time) => super::unix_seconds::serialize(time, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::unix_seconds")] SystemTime);
+
+        let wrapper = Option::<Wrapper>::deserialize(deserializer)?;
+        Ok(wrapper.map(|Wrapper(time)| time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_unix_seconds, try_from_unix_seconds, unix_seconds};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn in_range_timestamps() {
+        assert_eq!(from_unix_seconds(0), UNIX_EPOCH);
+        assert_eq!(
+            try_from_unix_seconds(1_700_000_000),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn out_of_range_timestamps() {
+        assert_eq!(try_from_unix_seconds(u64::MAX), None);
+
+        let mut deserializer = serde_json::Deserializer::from_str("18446744073709551615");
+        let err = unix_seconds::deserialize(&mut deserializer).unwrap_err();
+        assert!(err.to_string().contains("timestamp out of range"));
+    }
+}
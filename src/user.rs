@@ -1,7 +1,25 @@
+#[cfg(feature = "axum")]
+use crate::headers::WithConfig;
 #[cfg(feature = "headers")]
 use crate::headers::{
-    extract, extract_opt, OAuthProviderSlug, OAuthUserEmail, OAuthUserId, UserEmail,
-    UserFamilyName, UserGivenName, UserId, UserIsAdmin, UserSession,
+    extract, extract_opt, extract_opt_or_none, extract_or, extract_with, resolve_unknown,
+    try_insert, validate, write_with, written_by_newer, AuthAuthenticatedAt, Config,
+    ContextVersion, EncodeError, ErrorKind, ImpersonationReason, ImpersonationStartedAt,
+    ImpersonatorId, OAuthProviderSlug, OAuthUserEmail, OAuthUserId, ServiceName,
+    ServicePermissions, SessionCreatedAt, SessionExpiresAt, SessionId, SessionProvider, UserEmail,
+    UserFamilyName, UserGivenName, UserId as UserIdHeader, UserIsAdmin, UserMemberships,
+    UserRoleValidFrom, UserRoleValidUntil, UserSession, PROTOCOL_VERSION, USER_HEADERS,
+};
+use crate::{
+    id::UserId,
+    permission::Permission,
+    scope::Scope,
+    validated::{is_token, Email, OAuthProvider, Slug},
+};
+#[cfg(feature = "headers")]
+use crate::{
+    time::{Clock, SystemClock},
+    validated::ValidationError,
 };
 #[cfg(feature = "axum")]
 use axum_core::{
@@ -14,8 +32,11 @@ use headers::HeaderMapExt;
 use http::request::Parts;
 #[cfg(feature = "headers")]
 use http::HeaderMap;
-use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    borrow::Cow,
+    time::{Duration, SystemTime},
+};
 
 /// Query parameters for fetching the user context
 #[derive(Debug, Deserialize, Serialize)]
@@ -27,8 +48,9 @@ pub struct UserParams<'p> {
 
 /// Information about the requesting user
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
 #[serde(tag = "type", rename_all = "kebab-case")]
+#[allow(clippy::large_enum_variant)]
 pub enum User {
     /// The user is unauthenticated
     Unauthenticated,
@@ -39,42 +61,121 @@ pub enum User {
     RegistrationNeeded(UserRegistrationNeeded),
     /// The user is fully authenticated
     Authenticated(AuthenticatedUser),
+    /// The request is being made by another service
+    Service(ServiceUser),
 }
 
 #[cfg(feature = "headers")]
 impl User {
     /// Serialize the context into request headers
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value cannot be represented as a header, see [`User::try_into_headers`].
     pub fn into_headers(self) -> HeaderMap {
+        self.try_into_headers()
+            .expect("user must be representable as headers")
+    }
+
+    /// Serialize the context into request headers, failing if any value cannot be represented
+    pub fn try_into_headers(self) -> Result<HeaderMap, EncodeError> {
+        self.try_into_headers_with(&Config::default())
+    }
+
+    /// Serialize the context into request headers using the provided configuration
+    pub fn try_into_headers_with(self, config: &Config) -> Result<HeaderMap, EncodeError> {
         let mut map = HeaderMap::with_capacity(1);
-        self.write_headers(&mut map);
-        map
+        self.try_write_headers_with(&mut map, config)?;
+        Ok(map)
     }
 
     /// Write the context to request headers
+    ///
+    /// # Panics
+    ///
+    /// Panics if any value cannot be represented as a header, see [`User::try_write_headers`].
     pub fn write_headers(self, headers: &mut HeaderMap) {
-        match self {
-            User::Unauthenticated => headers.typed_insert(UserSession::Unauthenticated),
-            User::OAuth => headers.typed_insert(UserSession::OAuth),
-            User::RegistrationNeeded(context) => {
-                headers.typed_insert(UserSession::RegistrationNeeded);
-                context.write_headers(headers);
-            }
-            User::Authenticated(context) => {
-                headers.typed_insert(UserSession::Authenticated);
-                context.write_headers(headers);
+        self.try_write_headers(headers)
+            .expect("user must be representable as headers")
+    }
+
+    /// Write the context to request headers, failing if any value cannot be represented
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        self.try_write_headers_with(headers, &Config::default())
+    }
+
+    /// Write the context to request headers using the provided configuration
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn try_write_headers_with(
+        self,
+        headers: &mut HeaderMap,
+        config: &Config,
+    ) -> Result<(), EncodeError> {
+        self.try_write_headers_with_clock(headers, config, &SystemClock)
+    }
+
+    /// Write the context to request headers using the provided configuration, reading the time
+    /// from the clock
+    ///
+    /// The clock decides whether a time-boxed role is written, see
+    /// [`AuthenticatedUser::role_active_at`]. Nothing is written if an error is returned.
+    pub fn try_write_headers_with_clock(
+        self,
+        headers: &mut HeaderMap,
+        config: &Config,
+        clock: &dyn Clock,
+    ) -> Result<(), EncodeError> {
+        write_with(headers, config, |headers| {
+            headers.typed_insert(ContextVersion::from(PROTOCOL_VERSION));
+            match self {
+                User::Unauthenticated => headers.typed_insert(UserSession::Unauthenticated),
+                User::OAuth => headers.typed_insert(UserSession::OAuth),
+                User::RegistrationNeeded(context) => {
+                    headers.typed_insert(UserSession::RegistrationNeeded);
+                    context.try_write_headers(headers)?;
+                }
+                User::Authenticated(context) => {
+                    headers.typed_insert(UserSession::Authenticated);
+                    context.try_write_headers(headers, clock.now())?;
+                }
+                User::Service(context) => {
+                    headers.typed_insert(UserSession::Service);
+                    context.try_write_headers(headers)?;
+                }
             }
-        }
+
+            Ok(())
+        })
     }
 }
 
 #[cfg(feature = "headers")]
-impl TryFrom<&HeaderMap> for User {
-    type Error = crate::Error;
+impl User {
+    /// Extract the context from request headers using the provided configuration
+    ///
+    /// The [`TryFrom`] implementation is equivalent to a non-strict configuration without a
+    /// prefix.
+    pub fn from_headers(headers: &HeaderMap, config: &Config) -> Result<Self, crate::Error> {
+        extract_with(headers, config, &USER_HEADERS, |headers| {
+            Self::decode(headers, config.keeps_unknown_values())
+        })
+    }
 
-    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
-        let session = extract::<UserSession>(headers)?;
+    /// Extract the context from request headers, tolerating unknown values if `keep_unknown` is
+    /// set
+    fn decode(headers: &HeaderMap, keep_unknown: bool) -> Result<Self, crate::Error> {
+        let newer = written_by_newer(headers)?;
+        let session = extract_or(headers, newer, || UserSession::Unauthenticated)?;
 
         Ok(match session {
+            // there is no way to know what an unknown session grants, so it gets nothing
+            UserSession::Unknown(_) => {
+                resolve_unknown(session, keep_unknown, newer)?;
+                Self::Unauthenticated
+            }
             UserSession::Unauthenticated => Self::Unauthenticated,
             UserSession::OAuth => Self::OAuth,
             UserSession::RegistrationNeeded => {
@@ -82,13 +183,26 @@ impl TryFrom<&HeaderMap> for User {
                 Self::RegistrationNeeded(context)
             }
             UserSession::Authenticated => {
-                let context = AuthenticatedUser::try_from(headers)?;
+                let context = AuthenticatedUser::decode(headers, keep_unknown)?;
                 Self::Authenticated(context)
             }
+            UserSession::Service => {
+                let context = ServiceUser::try_from(headers)?;
+                Self::Service(context)
+            }
         })
     }
 }
 
+#[cfg(feature = "headers")]
+impl TryFrom<&HeaderMap> for User {
+    type Error = crate::Error;
+
+    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
+        Self::decode(headers, false)
+    }
+}
+
 #[cfg(feature = "axum")]
 #[async_trait::async_trait]
 impl<S> FromRequestParts<S> for User
@@ -98,46 +212,72 @@ where
     type Rejection = crate::Error;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        Self::try_from(&parts.headers)
+        match parts.extensions.get::<Config>() {
+            Some(config) => Self::from_headers(&parts.headers, config),
+            None => Self::from_headers(&parts.headers, &Config::default()),
+        }
     }
 }
 
 #[cfg(feature = "axum")]
 impl IntoResponseParts for User {
-    type Error = std::convert::Infallible;
+    type Error = EncodeError;
+
+    fn into_response_parts(self, res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        WithConfig(self, &Config::default()).into_response_parts(res)
+    }
+}
+
+#[cfg(feature = "axum")]
+impl IntoResponse for User {
+    fn into_response(self) -> Response {
+        WithConfig(self, &Config::default()).into_response()
+    }
+}
+
+#[cfg(feature = "axum")]
+impl IntoResponseParts for WithConfig<'_, User> {
+    type Error = EncodeError;
 
     fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
-        self.write_headers(res.headers_mut());
+        let WithConfig(user, config) = self;
+        user.try_write_headers_with(res.headers_mut(), config)?;
         Ok(res)
     }
 }
 
 #[cfg(feature = "axum")]
-impl IntoResponse for User {
+impl IntoResponse for WithConfig<'_, User> {
     fn into_response(self) -> Response {
-        self.into_headers().into_response()
+        let WithConfig(user, config) = self;
+        match user.try_into_headers_with(config) {
+            Ok(headers) => headers.into_response(),
+            Err(e) => e.into_response(),
+        }
     }
 }
 
 /// Details about a user that needs to complete their registration
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
 pub struct UserRegistrationNeeded {
-    /// The slug of the provider the user authenticated with
-    pub provider: String,
+    /// The provider the user authenticated with
+    pub provider: OAuthProvider,
     /// The user's ID according to the provider
     pub id: String,
     /// The user's primary email from the provider
-    pub email: String,
+    pub email: Email,
 }
 
 #[cfg(feature = "headers")]
 impl UserRegistrationNeeded {
     /// Write the context to request headers
-    fn write_headers(self, headers: &mut HeaderMap) {
-        headers.typed_insert(OAuthProviderSlug::from(self.provider));
-        headers.typed_insert(OAuthUserId::from(self.id));
-        headers.typed_insert(OAuthUserEmail::from(self.email));
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        headers.typed_insert(OAuthProviderSlug::from(self.provider.as_str().to_owned()));
+        try_insert(headers, OAuthUserId::from(self.id))?;
+        headers.typed_insert(OAuthUserEmail::from(self.email.into_inner()));
+
+        Ok(())
     }
 }
 
@@ -146,48 +286,178 @@ impl TryFrom<&HeaderMap> for UserRegistrationNeeded {
     type Error = crate::Error;
 
     fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
-        let provider = extract::<OAuthProviderSlug>(headers)?;
+        let provider = validate(extract::<OAuthProviderSlug>(headers)?)?;
         let id = extract::<OAuthUserId>(headers)?;
-        let email = extract::<OAuthUserEmail>(headers)?;
+        let email = validate(extract::<OAuthUserEmail>(headers)?)?;
 
         Ok(Self {
-            provider: provider.into_inner(),
+            provider,
             id: id.into_inner(),
-            email: email.into_inner(),
+            email,
         })
     }
 }
 
 /// Details about an authenticated user
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[cfg_attr(test, derive(Eq, PartialEq))]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
 pub struct AuthenticatedUser {
     /// The user's ID
-    pub id: i32,
+    pub id: UserId,
     /// The user's given/first name
     pub given_name: String,
     /// The user's family/last name
     pub family_name: String,
     /// The user's primary email
-    pub email: String,
+    pub email: Email,
     /// The user's role for the scope
     pub role: Option<UserRole>,
+    /// When the role is in effect, if it was only granted for a limited time
+    ///
+    /// Readers that predate time-boxed grants ignore the window, so roles that are not in effect
+    /// are omitted when writing the context to headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_window: Option<RoleWindow>,
     /// Whether the user is an admin
     pub is_admin: bool,
+    /// Where the user is in the participant lifecycle, for event scoped requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub participant_status: Option<ParticipantStatus>,
+    /// The events the user is a member of, if they were requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memberships: Option<Vec<EventMembership>>,
+    /// Who is really acting, if the user is being impersonated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<Impersonator>,
+    /// Details about the session the request was made with, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionMetadata>,
+    /// How strongly the user proved their identity, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assurance: Option<Assurance>,
+}
+
+impl AuthenticatedUser {
+    /// The user's role as it applies to the scope
+    ///
+    /// Roles are only granted for requests scoped to an event or an organization. As participants
+    /// and volunteers cannot affect anything at the organization level, [`UserRole::Participant`]
+    /// and [`UserRole::Volunteer`] are never granted for organization scoped requests.
+    ///
+    /// Roles outside of their [window](AuthenticatedUser::role_window) are treated as no role.
+    pub fn role_for(&self, scope: &Scope) -> Option<UserRole> {
+        self.role_for_at(scope, SystemTime::now())
+    }
+
+    /// The user's role as it applies to the scope at a point in time
+    ///
+    /// See [`AuthenticatedUser::role_for`] for how the role applies to each scope.
+    pub fn role_for_at(&self, scope: &Scope, now: SystemTime) -> Option<UserRole> {
+        if !self.role_active_at(now) {
+            return None;
+        }
+
+        match (scope, &self.role) {
+            (Scope::Event(_), role) => role.clone(),
+            (Scope::Organization(_), Some(UserRole::Participant | UserRole::Volunteer)) => None,
+            (Scope::Organization(_), role) => role.clone(),
+            (Scope::Admin | Scope::User, _) => None,
+        }
+    }
+
+    /// Whether the user's role is in effect at a point in time
+    ///
+    /// Roles without a window are always in effect.
+    pub fn role_active_at(&self, now: SystemTime) -> bool {
+        self.role_window
+            .as_ref()
+            .map(|window| window.contains(now))
+            .unwrap_or(true)
+    }
+
+    /// The user's role for the event, according to their memberships
+    pub fn membership(&self, event: &str) -> Option<UserRole> {
+        self.memberships
+            .iter()
+            .flatten()
+            .find(|membership| membership.event == event)
+            .map(|membership| membership.role.clone())
+    }
+
+    /// The slugs of the events where the user has at least the role
+    ///
+    /// Memberships with an unknown role are never included.
+    pub fn events_with_role(&self, role: UserRole) -> impl Iterator<Item = &str> {
+        self.memberships
+            .iter()
+            .flatten()
+            .filter(move |membership| membership.role.is_at_least(&role))
+            .map(|membership| membership.event.as_str())
+    }
+
+    /// Whether someone else is acting as the user
+    pub fn is_impersonated(&self) -> bool {
+        self.impersonator.is_some()
+    }
+
+    /// Whether the user authenticated with at least the required assurance level
+    pub fn has_assurance(&self, level: AssuranceLevel) -> bool {
+        self.assurance
+            .as_ref()
+            .map(|assurance| assurance.level >= level)
+            .unwrap_or(false)
+    }
+
+    /// Whether the user authenticated within the maximum age
+    pub fn authenticated_within(&self, max_age: Duration, now: SystemTime) -> bool {
+        self.assurance
+            .as_ref()
+            .map(|assurance| assurance.age(now) <= max_age)
+            .unwrap_or(false)
+    }
 }
 
 #[cfg(feature = "headers")]
 impl AuthenticatedUser {
     /// Write the context to request headers
-    fn write_headers(self, headers: &mut HeaderMap) {
-        headers.typed_insert(UserId::from(self.id));
+    ///
+    /// Roles outside of their window are omitted, along with the window, since readers that
+    /// predate time-boxed grants would otherwise treat them as permanent.
+    fn try_write_headers(
+        self,
+        headers: &mut HeaderMap,
+        now: SystemTime,
+    ) -> Result<(), EncodeError> {
+        let role_active = self.role_active_at(now);
+
+        headers.typed_insert(UserIdHeader::from(self.id));
         headers.typed_insert(UserGivenName::from(self.given_name));
         headers.typed_insert(UserFamilyName::from(self.family_name));
-        headers.typed_insert(UserEmail::from(self.email));
-        if let Some(role) = self.role {
-            headers.typed_insert(role);
+        headers.typed_insert(UserEmail::from(self.email.into_inner()));
+        if let (Some(role), true) = (self.role, role_active) {
+            try_insert(headers, role)?;
+            if let Some(window) = self.role_window {
+                window.try_write_headers(headers)?;
+            }
         }
         headers.typed_insert(UserIsAdmin::from(self.is_admin));
+        if let Some(status) = self.participant_status {
+            headers.typed_insert(status);
+        }
+        if let Some(memberships) = self.memberships {
+            try_insert(headers, UserMemberships::from(memberships))?;
+        }
+        if let Some(impersonator) = self.impersonator {
+            impersonator.try_write_headers(headers)?;
+        }
+        if let Some(session) = self.session {
+            session.try_write_headers(headers)?;
+        }
+        if let Some(assurance) = self.assurance {
+            assurance.try_write_headers(headers)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -196,34 +466,405 @@ impl TryFrom<&HeaderMap> for AuthenticatedUser {
     type Error = crate::Error;
 
     fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
-        let id = extract::<UserId>(headers)?;
+        Self::decode(headers, false)
+    }
+}
+
+#[cfg(feature = "headers")]
+impl AuthenticatedUser {
+    /// Extract the context from request headers, keeping unknown roles if `keep_unknown` is set
+    fn decode(headers: &HeaderMap, keep_unknown: bool) -> Result<Self, crate::Error> {
+        let id = extract::<UserIdHeader>(headers)?;
         let given_name = extract::<UserGivenName>(headers)?;
         let family_name = extract::<UserFamilyName>(headers)?;
-        let email = extract::<UserEmail>(headers)?;
-        let role = extract_opt::<UserRole>(headers)?;
+        let email = validate(extract::<UserEmail>(headers)?)?;
+        let newer = written_by_newer(headers)?;
+        let role = match extract_opt_or_none::<UserRole>(headers, newer)? {
+            Some(role) if role.is_unknown() => resolve_unknown(role, keep_unknown, newer)?,
+            role => role,
+        };
+        let role_window = RoleWindow::try_from_optional(headers)?;
         let is_admin = extract::<UserIsAdmin>(headers)?;
+        let participant_status = extract_opt_or_none::<ParticipantStatus>(headers, newer)?;
+        let memberships = match extract_opt_or_none::<UserMemberships>(headers, newer)? {
+            // Only the memberships with a newer role are dropped, the rest are still known
+            Some(memberships) if memberships.has_unknown_role() && newer && !keep_unknown => {
+                Some(memberships.without_unknown_roles())
+            }
+            Some(memberships) if memberships.has_unknown_role() => {
+                resolve_unknown(memberships, keep_unknown, newer)?
+            }
+            memberships => memberships,
+        };
+        let memberships = memberships
+            .map(UserMemberships::try_into_inner)
+            .transpose()?;
+        let impersonator = Impersonator::try_from_optional(headers)?;
+        let session = SessionMetadata::try_from_optional(headers)?;
+        let assurance = Assurance::try_from_optional(headers)?;
 
         Ok(Self {
             id: id.into_inner(),
             given_name: given_name.into_inner(),
             family_name: family_name.into_inner(),
-            email: email.into_inner(),
+            email,
             role,
+            role_window,
             is_admin: is_admin.into_inner(),
+            participant_status,
+            memberships,
+            impersonator,
+            session,
+            assurance,
         })
     }
 }
 
-/// The role applied to the current user
+/// The period during which a role grant is in effect
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RoleWindow {
+    /// When the role takes effect
+    #[serde(with = "crate::time::unix_seconds")]
+    pub valid_from: SystemTime,
+    /// When the role expires
+    #[serde(with = "crate::time::unix_seconds")]
+    pub valid_until: SystemTime,
+}
+
+impl RoleWindow {
+    /// Whether the role is in effect at a point in time
+    pub fn contains(&self, now: SystemTime) -> bool {
+        self.valid_from <= now && now < self.valid_until
+    }
+}
+
+#[cfg(feature = "headers")]
+impl RoleWindow {
+    /// Write the context to request headers
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        headers.typed_insert(UserRoleValidFrom::from(self.valid_from));
+        headers.typed_insert(UserRoleValidUntil::from(self.valid_until));
+
+        Ok(())
+    }
+
+    /// Extract the window from the headers, if the role was granted for a limited time
+    ///
+    /// Both bounds are required once either is present, so a partial window is never mistaken
+    /// for a permanent grant.
+    fn try_from_optional(headers: &HeaderMap) -> Result<Option<Self>, crate::Error> {
+        if extract_opt::<UserRoleValidFrom>(headers)?.is_none()
+            && extract_opt::<UserRoleValidUntil>(headers)?.is_none()
+        {
+            return Ok(None);
+        }
+        let valid_from = extract::<UserRoleValidFrom>(headers)?;
+        let valid_until = extract::<UserRoleValidUntil>(headers)?;
+
+        Ok(Some(Self {
+            valid_from: valid_from.into_inner(),
+            valid_until: valid_until.into_inner(),
+        }))
+    }
+}
+
+/// The real actor behind an impersonated user
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+pub struct Impersonator {
+    /// The ID of the user doing the impersonation
+    pub id: UserId,
+    /// Why the user is being impersonated
+    pub reason: String,
+    /// When the impersonation started
+    #[serde(with = "crate::time::unix_seconds")]
+    pub started_at: SystemTime,
+}
+
+#[cfg(feature = "headers")]
+impl Impersonator {
+    /// Write the context to request headers
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        headers.typed_insert(ImpersonatorId::from(self.id));
+        headers.typed_insert(ImpersonationReason::from(self.reason));
+        headers.typed_insert(ImpersonationStartedAt::from(self.started_at));
+
+        Ok(())
+    }
+
+    /// Extract the impersonator from the headers, if the user is being impersonated
+    fn try_from_optional(headers: &HeaderMap) -> Result<Option<Self>, crate::Error> {
+        let Some(id) = extract_opt::<ImpersonatorId>(headers)? else {
+            return Ok(None);
+        };
+        let reason = extract::<ImpersonationReason>(headers)?;
+        let started_at = extract::<ImpersonationStartedAt>(headers)?;
+
+        Ok(Some(Self {
+            id: id.into_inner(),
+            reason: reason.into_inner(),
+            started_at: started_at.into_inner(),
+        }))
+    }
+}
+
+/// Details about the session an authenticated user made the request with
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+pub struct SessionMetadata {
+    /// The session's ID
+    pub id: String,
+    /// When the session was created
+    #[serde(with = "crate::time::unix_seconds")]
+    pub created_at: SystemTime,
+    /// When the session expires
+    #[serde(with = "crate::time::unix_seconds")]
+    pub expires_at: SystemTime,
+    /// The OAuth provider the user logged in with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<OAuthProvider>,
+}
+
+impl SessionMetadata {
+    /// How long ago the session was created
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.created_at).unwrap_or_default()
+    }
+
+    /// Whether the session has expired
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[cfg(feature = "headers")]
+impl SessionMetadata {
+    /// Write the context to request headers
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        try_insert(headers, SessionId::from(self.id))?;
+        headers.typed_insert(SessionCreatedAt::from(self.created_at));
+        headers.typed_insert(SessionExpiresAt::from(self.expires_at));
+        if let Some(provider) = self.provider {
+            headers.typed_insert(SessionProvider::from(provider.as_str().to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Extract the session metadata from the headers, if it was provided
+    fn try_from_optional(headers: &HeaderMap) -> Result<Option<Self>, crate::Error> {
+        let Some(id) = extract_opt::<SessionId>(headers)? else {
+            return Ok(None);
+        };
+        let created_at = extract::<SessionCreatedAt>(headers)?;
+        let expires_at = extract::<SessionExpiresAt>(headers)?;
+        let provider = match extract_opt::<SessionProvider>(headers)?
+            .map(validate)
+            .transpose()
+        {
+            Err(crate::Error {
+                kind: ErrorKind::Invalid(ValidationError::UnknownProvider),
+                ..
+            }) if written_by_newer(headers)? => None,
+            result => result?,
+        };
+
+        Ok(Some(Self {
+            id: id.into_inner(),
+            created_at: created_at.into_inner(),
+            expires_at: expires_at.into_inner(),
+            provider,
+        }))
+    }
+}
+
+/// The user's role within an event other than the one the request is scoped to
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EventMembership {
+    /// The event slug
+    pub event: Slug,
+    /// The user's role for the event
+    pub role: UserRole,
+}
+
+/// How strongly an authenticated user proved their identity
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+pub struct Assurance {
+    /// The strongest method the user authenticated with
+    pub level: AssuranceLevel,
+    /// When the user last authenticated at this level
+    #[serde(with = "crate::time::unix_seconds")]
+    pub authenticated_at: SystemTime,
+}
+
+impl Assurance {
+    /// How long ago the user authenticated at this level
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.authenticated_at)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "headers")]
+impl Assurance {
+    /// Write the context to request headers
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        headers.typed_insert(self.level);
+        headers.typed_insert(AuthAuthenticatedAt::from(self.authenticated_at));
+
+        Ok(())
+    }
+
+    /// Extract the assurance from the headers, if it was provided
+    fn try_from_optional(headers: &HeaderMap) -> Result<Option<Self>, crate::Error> {
+        let newer = written_by_newer(headers)?;
+        let Some(level) = extract_opt_or_none::<AssuranceLevel>(headers, newer)? else {
+            return Ok(None);
+        };
+        let authenticated_at = extract::<AuthAuthenticatedAt>(headers)?;
+
+        Ok(Some(Self {
+            level,
+            authenticated_at: authenticated_at.into_inner(),
+        }))
+    }
+}
+
+/// The method a user authenticated with, from weakest to strongest
 ///
-/// Transmitted in the `User-Role` header
+/// Transmitted in the `Auth-Assurance-Level` header
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssuranceLevel {
+    /// A username and password
+    Password,
+    /// An external OAuth provider
+    #[serde(rename = "oauth")]
+    OAuth,
+    /// Multiple factors
+    Mfa,
+}
+
+impl AssuranceLevel {
+    /// The name of the assurance level as it is transmitted
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Password => "password",
+            Self::OAuth => "oauth",
+            Self::Mfa => "mfa",
+        }
+    }
+}
+
+/// Where a participant is in the application lifecycle for an event
+///
+/// Transmitted in the `User-Participant-Status` header
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParticipantStatus {
+    /// The participant submitted an application that has not been reviewed
+    Applied,
+    /// The participant's application was waitlisted
+    Waitlisted,
+    /// The participant's application was rejected
+    Rejected,
+    /// The participant's application was accepted
+    Accepted,
+    /// The participant confirmed their attendance
+    Confirmed,
+    /// The participant checked in at the event
+    CheckedIn,
+}
+
+impl ParticipantStatus {
+    /// The name of the status as it is transmitted
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Applied => "applied",
+            Self::Waitlisted => "waitlisted",
+            Self::Rejected => "rejected",
+            Self::Accepted => "accepted",
+            Self::Confirmed => "confirmed",
+            Self::CheckedIn => "checked-in",
+        }
+    }
+}
+
+/// Details about a service making a request on its own behalf
+///
+/// Services are never administrators and never have a role. Instead, they are granted a fixed set
+/// of permissions.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(any(test, feature = "testing"), derive(Eq, PartialEq))]
+pub struct ServiceUser {
+    /// The name of the service
+    pub name: String,
+    /// The permissions granted to the service
+    pub permissions: Vec<Permission>,
+}
+
+impl ServiceUser {
+    /// Check whether the service was granted the permission
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+#[cfg(feature = "headers")]
+impl ServiceUser {
+    /// Write the context to request headers
+    fn try_write_headers(self, headers: &mut HeaderMap) -> Result<(), EncodeError> {
+        try_insert(headers, ServiceName::from(self.name))?;
+        headers.typed_insert(ServicePermissions::from(self.permissions));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "headers")]
+impl TryFrom<&HeaderMap> for ServiceUser {
+    type Error = crate::Error;
+
+    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
+        let name = extract::<ServiceName>(headers)?;
+        let newer = written_by_newer(headers)?;
+        let permissions = extract_or(headers, newer, || ServicePermissions::from(Vec::new()))?;
+
+        Ok(Self {
+            name: name.into_inner(),
+            permissions: permissions.into_inner(),
+        })
+    }
+}
+
+/// The role applied to the current user
+///
+/// For event scoped requests, this is the user's role within the event. For organization scoped
+/// requests, this is the user's role within the organization. See [`AuthenticatedUser::role_for`]
+/// for how the role applies to each scope.
+///
+/// Transmitted in the `User-Role` header, and (de)serialized by the same name, see
+/// [`UserRole::as_str`]
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum UserRole {
+    /// A role introduced by a newer version of the protocol
+    ///
+    /// Only produced when opted into with
+    /// [`Config::unknown_values`](crate::headers::Config::unknown_values). Unknown roles sort
+    /// below every known role and never satisfy [`UserRole::is_at_least`] or the default
+    /// [`RolePermissions`](crate::RolePermissions), so they are denied by default.
+    Unknown(String),
     /// A participant of an event
     ///
     /// Cannot affect anything at the organization level, only has permissions for the individual
     /// event.
     Participant,
+    /// A day-of volunteer at an event
+    ///
+    /// Can help run the event, i.e. checking participants in, but cannot see applications.
+    /// Like participants, volunteers cannot affect anything at the organization level.
+    Volunteer,
     /// A normal user within the organization
     Organizer,
     /// An elevated user within the organization
@@ -231,41 +872,164 @@ pub enum UserRole {
     /// Has more permissions than an organizer but less than a director. Managers are able to
     /// event and organization settings.
     Manager,
-    /// The highest permissions in an organization
+    /// A senior user within the organization
     ///
     /// Equivalent to an owner, but cannot modify billing information or delete the organization.
     Director,
+    /// The highest permissions in an organization
+    ///
+    /// The only role able to modify billing information or delete the organization.
+    Owner,
 }
 
-#[cfg(all(test, feature = "headers"))]
-mod tests {
-    use super::{AuthenticatedUser, User, UserRegistrationNeeded, UserRole};
-    use crate::{error_test_cases, headers, headers::ErrorKind};
+impl UserRole {
+    /// The name of the role as it is transmitted
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Unknown(role) => role,
+            Self::Participant => "participant",
+            Self::Volunteer => "volunteer",
+            Self::Organizer => "organizer",
+            Self::Manager => "manager",
+            Self::Director => "director",
+            Self::Owner => "owner",
+        }
+    }
 
-    #[test]
-    fn try_from_valid_unauthenticated() {
-        let headers = headers! {
-            "User-Session" => "unauthenticated",
-        };
+    /// Whether the role is not known to this version
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
 
-        let context = User::try_from(&headers).unwrap();
-        assert!(matches!(context, User::Unauthenticated));
+    /// Whether the role is the same as or above the other role
+    ///
+    /// Unknown roles cannot be placed in the hierarchy, so the comparison always fails if either
+    /// role is unknown.
+    pub fn is_at_least(&self, role: &UserRole) -> bool {
+        !self.is_unknown() && !role.is_unknown() && self >= role
     }
 
-    #[test]
-    fn try_from_valid_oauth() {
-        let headers = headers! {
-            "User-Session" => "oauth",
-        };
+    /// Parse a role from its transmitted name
+    ///
+    /// Well-formed names that are not known are parsed as [`UserRole::Unknown`].
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match Self::parse_known(value) {
+            Some(role) => Some(role),
+            None if is_token(value) => Some(Self::Unknown(value.to_owned())),
+            None => None,
+        }
+    }
 
-        let context = User::try_from(&headers).unwrap();
-        assert!(matches!(context, User::OAuth));
+    /// Parse a role known to this version from its transmitted name
+    pub(crate) fn parse_known(value: &str) -> Option<Self> {
+        Some(match value {
+            "participant" => Self::Participant,
+            "volunteer" => Self::Volunteer,
+            "organizer" => Self::Organizer,
+            "manager" => Self::Manager,
+            "director" => Self::Director,
+            "owner" => Self::Owner,
+            _ => return None,
+        })
     }
+}
 
-    #[test]
-    fn try_from_valid_registration_needed() {
-        let headers = headers! {
-            "User-Session" => "registration-needed",
+impl Serialize for UserRole {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for UserRole {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Self::parse(&name).ok_or_else(|| D::Error::custom(format!("invalid role `{name}`")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthenticatedUser, User, UserRole};
+    use crate::{EventScope, OrganizationId, OrganizationScope, Scope, Slug};
+
+    #[test]
+    fn role_for_scope() {
+        let user = |role| AuthenticatedUser::from(User::authenticated().id(79).role(role));
+        let event = Scope::Event(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: None,
+        });
+        let organization = Scope::Organization(OrganizationScope {
+            organization_id: OrganizationId::from(5),
+        });
+
+        let participant = user(UserRole::Participant);
+        assert_eq!(participant.role_for(&event), Some(UserRole::Participant));
+        assert_eq!(participant.role_for(&organization), None);
+        assert_eq!(participant.role_for(&Scope::User), None);
+
+        let volunteer = user(UserRole::Volunteer);
+        assert_eq!(volunteer.role_for(&event), Some(UserRole::Volunteer));
+        assert_eq!(volunteer.role_for(&organization), None);
+
+        let owner = user(UserRole::Owner);
+        assert_eq!(owner.role_for(&organization), Some(UserRole::Owner));
+
+        let manager = user(UserRole::Manager);
+        assert_eq!(manager.role_for(&event), Some(UserRole::Manager));
+        assert_eq!(manager.role_for(&organization), Some(UserRole::Manager));
+        assert_eq!(manager.role_for(&Scope::Admin), None);
+    }
+}
+
+#[cfg(all(test, feature = "headers"))]
+mod headers_tests {
+    use super::{
+        Assurance, AssuranceLevel, AuthenticatedUser, EventMembership, Impersonator,
+        ParticipantStatus, RoleWindow, ServiceUser, SessionMetadata, User, UserRegistrationNeeded,
+        UserRole,
+    };
+    use crate::{
+        error_test_cases, headers,
+        headers::{Config, ErrorKind, PROTOCOL_VERSION},
+        time::{from_unix_seconds, FixedClock},
+        Email, EventScope, OAuthProvider, OrganizationId, Permission, Scope, Slug, UserId,
+        ValidationError,
+    };
+    use http::HeaderMap;
+    use std::time::Duration;
+
+    #[test]
+    fn try_from_valid_unauthenticated() {
+        let headers = headers! {
+            "User-Session" => "unauthenticated",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        assert!(matches!(context, User::Unauthenticated));
+    }
+
+    #[test]
+    fn try_from_valid_oauth() {
+        let headers = headers! {
+            "User-Session" => "oauth",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        assert!(matches!(context, User::OAuth));
+    }
+
+    #[test]
+    fn try_from_valid_registration_needed() {
+        let headers = headers! {
+            "User-Session" => "registration-needed",
             "OAuth-Provider-Slug" => "google",
             "OAuth-User-ID" => "1234567890",
             "OAuth-User-Email" => "hello@world.com",
@@ -276,7 +1040,7 @@ mod tests {
             panic!("expected Context::RegistrationNeeded, got {:?}", context);
         };
 
-        assert_eq!(context.provider, "google");
+        assert_eq!(context.provider, OAuthProvider::Google);
         assert_eq!(context.id, "1234567890");
         assert_eq!(context.email, "hello@world.com");
     }
@@ -298,13 +1062,74 @@ mod tests {
             panic!("expected Context::Authenticated, got {:?}", context);
         };
 
-        assert_eq!(context.id, 55);
+        assert_eq!(context.id, UserId::from(55));
         assert_eq!(context.given_name, "John");
         assert_eq!(context.family_name, "Doe");
         assert_eq!(context.email, "john.doe@gmail.com");
         assert!(context.is_admin);
     }
 
+    #[test]
+    fn try_from_valid_service() {
+        let headers = headers! {
+            "User-Session" => "service",
+            "Service-Name" => "mailer",
+            "Service-Permissions" => "read-applications, check-in",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Service(context) = context else {
+            panic!("expected Context::Service, got {:?}", context);
+        };
+
+        assert_eq!(context.name, "mailer");
+        assert_eq!(
+            context.permissions,
+            vec![Permission::ReadApplications, Permission::CheckIn]
+        );
+    }
+
+    #[test]
+    fn try_from_service_without_permissions() {
+        let headers = headers! {
+            "User-Session" => "service",
+            "Service-Name" => "mailer",
+            "Service-Permissions" => "",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Service(context) = context else {
+            panic!("expected Context::Service, got {:?}", context);
+        };
+        assert!(context.permissions.is_empty());
+    }
+
+    error_test_cases! {
+        for User;
+        try_from_service_missing_name(
+            "User-Session" => "service",
+            "Service-Permissions" => "check-in",
+        ) => {
+            header: "service-name",
+            kind: ErrorKind::Missing,
+        };
+        try_from_service_missing_permissions(
+            "User-Session" => "service",
+            "Service-Name" => "mailer",
+        ) => {
+            header: "service-permissions",
+            kind: ErrorKind::Missing,
+        };
+        try_from_service_unknown_permission(
+            "User-Session" => "service",
+            "Service-Name" => "mailer",
+            "Service-Permissions" => "check-in,launch-rockets",
+        ) => {
+            header: "service-permissions",
+            kind: ErrorKind::Error(_),
+        };
+    }
+
     error_test_cases! {
         for User;
         try_from_missing_session_state() => {
@@ -317,6 +1142,43 @@ mod tests {
         };
     }
 
+    error_test_cases! {
+        for User;
+        try_from_unknown_session_same_version(
+            "Context-Version" => PROTOCOL_VERSION.to_string(),
+            "User-Session" => "passkey-pending",
+        ) => {
+            header: "user-session",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_unknown_session_without_version(
+            "User-Session" => "passkey-pending",
+        ) => {
+            header: "user-session",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_unknown_role_same_version(
+            "Context-Version" => PROTOCOL_VERSION.to_string(),
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "superhero",
+            "User-Is-Admin" => "false",
+        ) => {
+            header: "user-role",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_invalid_version(
+            "Context-Version" => "latest",
+            "User-Session" => "unauthenticated",
+        ) => {
+            header: "context-version",
+            kind: ErrorKind::Error(_),
+        };
+    }
+
     error_test_cases! {
         for User;
         try_from_registration_needed_missing_oauth_provider(
@@ -336,6 +1198,24 @@ mod tests {
             header: "oauth-provider-slug",
             kind: ErrorKind::Error(_),
         };
+        try_from_registration_needed_unknown_oauth_provider(
+            "User-Session" => "registration-needed",
+            "OAuth-Provider-Slug" => "myspace",
+            "OAuth-User-ID" => "1234567890",
+            "OAuth-User-Email" => "hello@world.com",
+        ) => {
+            header: "oauth-provider-slug",
+            kind: ErrorKind::Invalid(ValidationError::UnknownProvider),
+        };
+        try_from_registration_needed_invalid_user_email(
+            "User-Session" => "registration-needed",
+            "OAuth-Provider-Slug" => "google",
+            "OAuth-User-ID" => "1234567890",
+            "OAuth-User-Email" => "hello.world.com",
+        ) => {
+            header: "oauth-user-email",
+            kind: ErrorKind::Invalid(ValidationError::InvalidFormat(_)),
+        };
         try_from_registration_needed_missing_user_id(
             "User-Session" => "registration-needed",
             "OAuth-Provider-Slug" => "google",
@@ -404,6 +1284,63 @@ mod tests {
             header: "user-id",
             kind: ErrorKind::Error(_),
         };
+        try_from_authenticated_invalid_percent_encoding(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "UTF-8''J%C3%Bxhn",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "true",
+        ) => {
+            header: "user-given-name",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_authenticated_truncated_percent_encoding(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "UTF-8''Doe%C",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "true",
+        ) => {
+            header: "user-family-name",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_authenticated_percent_encoded_invalid_utf8(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "UTF-8''J%FFhn",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "true",
+        ) => {
+            header: "user-given-name",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_authenticated_invalid_email(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail",
+            "User-Role" => "organizer",
+            "User-Is-Admin" => "true",
+        ) => {
+            header: "user-email",
+            kind: ErrorKind::Invalid(ValidationError::InvalidFormat(_)),
+        };
+        try_from_authenticated_membership_invalid_slug(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "true",
+            "User-Memberships" => "Waffle_Hacks=manager",
+        ) => {
+            header: "user-memberships",
+            kind: ErrorKind::Invalid(ValidationError::InvalidCharacter('W')),
+        };
         try_from_authenticated_missing_given_name(
             "User-Session" => "authenticated",
             "User-ID" => "55",
@@ -550,57 +1487,918 @@ mod tests {
     }
 
     #[test]
-    fn into_headers_unauthenticated() {
-        let headers = User::Unauthenticated.into_headers();
-        assert_eq!(headers.get("user-session").unwrap(), "unauthenticated");
+    fn try_from_authenticated_with_participant_status() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "participant",
+            "User-Is-Admin" => "false",
+            "User-Participant-Status" => "checked-in",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(
+            context.participant_status,
+            Some(ParticipantStatus::CheckedIn)
+        );
     }
 
-    #[test]
-    fn into_headers_oauth() {
-        let headers = User::OAuth.into_headers();
-        assert_eq!(headers.get("user-session").unwrap(), "oauth");
+    error_test_cases! {
+        for User;
+        try_from_authenticated_invalid_participant_status(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "participant",
+            "User-Is-Admin" => "false",
+            "User-Participant-Status" => "graduated",
+        ) => {
+            header: "user-participant-status",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_authenticated_role_window_missing_valid_from(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "organizer",
+            "User-Is-Admin" => "false",
+            "User-Role-Valid-Until" => "1700172800",
+        ) => {
+            header: "user-role-valid-from",
+            kind: ErrorKind::Missing,
+        };
+        try_from_authenticated_role_window_missing_valid_until(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "organizer",
+            "User-Is-Admin" => "false",
+            "User-Role-Valid-From" => "1700000000",
+        ) => {
+            header: "user-role-valid-until",
+            kind: ErrorKind::Missing,
+        };
+        try_from_authenticated_role_window_out_of_range(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "organizer",
+            "User-Is-Admin" => "false",
+            "User-Role-Valid-From" => "1700000000",
+            "User-Role-Valid-Until" => "18446744073709551615",
+        ) => {
+            header: "user-role-valid-until",
+            kind: ErrorKind::Error(_),
+        };
     }
 
     #[test]
-    fn into_headers_registration_needed() {
-        let context = User::RegistrationNeeded(UserRegistrationNeeded {
-            provider: String::from("google"),
-            id: String::from("01234567890"),
-            email: String::from("hello@world.com"),
-        });
-        let headers = context.into_headers();
+    fn try_from_authenticated_with_memberships() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "User-Memberships" => "wafflehacks-2023=director, wafflehacks-2024=organizer",
+        };
 
-        assert_eq!(headers.get("user-session").unwrap(), "registration-needed");
-        assert_eq!(headers.get("oauth-provider-slug").unwrap(), "google");
-        assert_eq!(headers.get("oauth-user-id").unwrap(), "01234567890");
-        assert_eq!(headers.get("oauth-user-email").unwrap(), "hello@world.com");
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(
+            context.memberships,
+            Some(vec![
+                EventMembership {
+                    event: Slug::new("wafflehacks-2023").unwrap(),
+                    role: UserRole::Director,
+                },
+                EventMembership {
+                    event: Slug::new("wafflehacks-2024").unwrap(),
+                    role: UserRole::Organizer,
+                },
+            ])
+        );
     }
 
     #[test]
-    fn into_headers_authenticated() {
-        let context = User::Authenticated(AuthenticatedUser {
-            id: 79,
-            given_name: String::from("John"),
-            family_name: String::from("Doe"),
-            email: String::from("john.doe@gmail.com"),
-            role: Some(UserRole::Manager),
-            is_admin: false,
-        });
-        let headers = context.into_headers();
+    fn try_from_authenticated_with_no_memberships() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "User-Memberships" => "",
+        };
 
-        assert_eq!(headers.get("user-session").unwrap(), "authenticated");
-        assert_eq!(headers.get("user-id").unwrap(), "79");
-        assert_eq!(headers.get("user-given-name").unwrap(), "John");
-        assert_eq!(headers.get("user-family-name").unwrap(), "Doe");
-        assert_eq!(headers.get("user-email").unwrap(), "john.doe@gmail.com");
-        assert_eq!(headers.get("user-is-admin").unwrap(), "false");
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.memberships, Some(Vec::new()));
     }
 
-    macro_rules! test_roundtrip {
-        ( $(
-            $name:ident ( $context:expr )
-        );+ $(;)? ) => {
-            $(
+    error_test_cases! {
+        for User;
+        try_from_authenticated_membership_missing_role(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "User-Memberships" => "wafflehacks-2023",
+        ) => {
+            header: "user-memberships",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_authenticated_membership_invalid_role(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "User-Memberships" => "wafflehacks-2023=developer",
+        ) => {
+            header: "user-memberships",
+            kind: ErrorKind::Error(_),
+        };
+    }
+
+    #[test]
+    fn membership_lookup() {
+        let user = AuthenticatedUser::from(
+            User::authenticated()
+                .id(79)
+                .membership("wafflehacks-2022", UserRole::Participant)
+                .membership("wafflehacks-2023", UserRole::Director)
+                .membership("wafflehacks-2024", UserRole::Organizer),
+        );
+
+        assert_eq!(
+            user.membership("wafflehacks-2023"),
+            Some(UserRole::Director)
+        );
+        assert_eq!(user.membership("wafflehacks-2021"), None);
+        assert_eq!(
+            user.events_with_role(UserRole::Organizer)
+                .collect::<Vec<_>>(),
+            vec!["wafflehacks-2023", "wafflehacks-2024"]
+        );
+    }
+
+    #[test]
+    fn try_from_authenticated_impersonated() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Impersonator-ID" => "3",
+            "Impersonation-Reason" => "debugging application",
+            "Impersonation-Started-At" => "1700000000",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(
+            context.impersonator,
+            Some(Impersonator {
+                id: UserId::from(3),
+                reason: String::from("debugging application"),
+                started_at: from_unix_seconds(1_700_000_000),
+            })
+        );
+    }
+
+    error_test_cases! {
+        for User;
+        try_from_impersonated_missing_reason(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Impersonator-ID" => "3",
+            "Impersonation-Started-At" => "1700000000",
+        ) => {
+            header: "impersonation-reason",
+            kind: ErrorKind::Missing,
+        };
+        try_from_impersonated_missing_started_at(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Impersonator-ID" => "3",
+            "Impersonation-Reason" => "debugging application",
+        ) => {
+            header: "impersonation-started-at",
+            kind: ErrorKind::Missing,
+        };
+        try_from_impersonated_invalid_started_at(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Impersonator-ID" => "3",
+            "Impersonation-Reason" => "debugging application",
+            "Impersonation-Started-At" => "yesterday",
+        ) => {
+            header: "impersonation-started-at",
+            kind: ErrorKind::Error(_),
+        };
+    }
+
+    #[test]
+    fn try_from_authenticated_with_session() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Session-ID" => "abc123",
+            "Session-Created-At" => "1700000000",
+            "Session-Expires-At" => "1700086400",
+            "Session-Provider" => "github",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(
+            context.session,
+            Some(SessionMetadata {
+                id: String::from("abc123"),
+                created_at: from_unix_seconds(1_700_000_000),
+                expires_at: from_unix_seconds(1_700_086_400),
+                provider: Some(OAuthProvider::GitHub),
+            })
+        );
+    }
+
+    #[test]
+    fn try_from_authenticated_session_is_optional() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.session, None);
+    }
+
+    error_test_cases! {
+        for User;
+        try_from_session_missing_created_at(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Session-ID" => "abc123",
+            "Session-Expires-At" => "1700086400",
+        ) => {
+            header: "session-created-at",
+            kind: ErrorKind::Missing,
+        };
+        try_from_session_missing_expires_at(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Session-ID" => "abc123",
+            "Session-Created-At" => "1700000000",
+        ) => {
+            header: "session-expires-at",
+            kind: ErrorKind::Missing,
+        };
+    }
+
+    #[test]
+    fn session_age_and_expiry() {
+        let session = SessionMetadata {
+            id: String::from("abc123"),
+            created_at: from_unix_seconds(1_700_000_000),
+            expires_at: from_unix_seconds(1_700_086_400),
+            provider: None,
+        };
+
+        let now = from_unix_seconds(1_700_000_600);
+        assert_eq!(session.age(now).as_secs(), 600);
+        assert!(!session.is_expired(now));
+        assert!(session.is_expired(from_unix_seconds(1_700_086_400)));
+        assert_eq!(session.age(from_unix_seconds(0)).as_secs(), 0);
+    }
+
+    #[test]
+    fn try_from_authenticated_with_assurance() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Auth-Assurance-Level" => "mfa",
+            "Auth-Authenticated-At" => "1700000000",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(
+            context.assurance,
+            Some(Assurance {
+                level: AssuranceLevel::Mfa,
+                authenticated_at: from_unix_seconds(1_700_000_000),
+            })
+        );
+    }
+
+    error_test_cases! {
+        for User;
+        try_from_invalid_assurance_level(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Auth-Assurance-Level" => "sms",
+            "Auth-Authenticated-At" => "1700000000",
+        ) => {
+            header: "auth-assurance-level",
+            kind: ErrorKind::Error(_),
+        };
+        try_from_assurance_missing_authenticated_at(
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Auth-Assurance-Level" => "password",
+        ) => {
+            header: "auth-authenticated-at",
+            kind: ErrorKind::Missing,
+        };
+    }
+
+    #[test]
+    fn assurance_checks() {
+        let user = AuthenticatedUser::from(
+            User::authenticated()
+                .id(79)
+                .assurance(AssuranceLevel::OAuth, from_unix_seconds(1_700_000_000)),
+        );
+        let now = from_unix_seconds(1_700_000_300);
+
+        assert!(user.has_assurance(AssuranceLevel::Password));
+        assert!(user.has_assurance(AssuranceLevel::OAuth));
+        assert!(!user.has_assurance(AssuranceLevel::Mfa));
+        assert!(user.authenticated_within(Duration::from_secs(300), now));
+        assert!(!user.authenticated_within(Duration::from_secs(299), now));
+
+        let user = AuthenticatedUser {
+            assurance: None,
+            ..user
+        };
+        assert!(!user.has_assurance(AssuranceLevel::Password));
+        assert!(!user.authenticated_within(Duration::MAX, now));
+    }
+
+    #[test]
+    fn into_headers_unauthenticated() {
+        let headers = User::Unauthenticated.into_headers();
+        assert_eq!(headers.get("user-session").unwrap(), "unauthenticated");
+    }
+
+    #[test]
+    fn into_headers_oauth() {
+        let headers = User::OAuth.into_headers();
+        assert_eq!(headers.get("user-session").unwrap(), "oauth");
+    }
+
+    #[test]
+    fn into_headers_registration_needed() {
+        let context = User::RegistrationNeeded(UserRegistrationNeeded {
+            provider: OAuthProvider::Google,
+            id: String::from("01234567890"),
+            email: Email::new("hello@world.com").unwrap(),
+        });
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("user-session").unwrap(), "registration-needed");
+        assert_eq!(headers.get("oauth-provider-slug").unwrap(), "google");
+        assert_eq!(headers.get("oauth-user-id").unwrap(), "01234567890");
+        assert_eq!(headers.get("oauth-user-email").unwrap(), "hello@world.com");
+    }
+
+    #[test]
+    fn into_headers_authenticated() {
+        let context = User::authenticated().id(79).role(UserRole::Manager).build();
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("user-session").unwrap(), "authenticated");
+        assert_eq!(headers.get("user-id").unwrap(), "79");
+        assert_eq!(headers.get("user-given-name").unwrap(), "John");
+        assert_eq!(headers.get("user-family-name").unwrap(), "Doe");
+        assert_eq!(headers.get("user-email").unwrap(), "john.doe@gmail.com");
+        assert_eq!(headers.get("user-is-admin").unwrap(), "false");
+    }
+
+    #[test]
+    fn into_headers_authenticated_percent_encodes_non_ascii() {
+        let context = User::authenticated()
+            .id(79)
+            .name("Jöhn", " Doe")
+            .email("jöhn.döe@gmail.cöm")
+            .build();
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("user-given-name").unwrap(), "UTF-8''J%C3%B6hn");
+        assert_eq!(headers.get("user-family-name").unwrap(), "UTF-8''%20Doe");
+        assert_eq!(
+            headers.get("user-email").unwrap(),
+            "UTF-8''j%C3%B6hn.d%C3%B6e%40gmail.c%C3%B6m"
+        );
+    }
+
+    #[test]
+    fn try_from_authenticated_accepts_both_encodings() {
+        let mut headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "UTF-8''J%C3%B6hn",
+            "User-Email" => "utf-8''john.doe%40gmail.com",
+            "User-Is-Admin" => "false",
+        };
+        headers.insert(
+            "User-Family-Name",
+            http::HeaderValue::from_bytes("Döe".as_bytes()).unwrap(),
+        );
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+
+        assert_eq!(context.given_name, "Jöhn");
+        assert_eq!(context.family_name, "Döe");
+        assert_eq!(context.email, "john.doe@gmail.com");
+    }
+
+    #[test]
+    fn roundtrip_arbitrary_names() {
+        let names = [
+            "",
+            " ",
+            "John ",
+            "\tJohn",
+            "Jo\r\nhn",
+            "Jo\0hn",
+            "100%",
+            "%41",
+            "UTF-8''John",
+            "utf-8''",
+            "🧇 Waffle",
+            "名前",
+            "\u{7f}\u{80}\u{ff}",
+        ];
+
+        for name in names {
+            let context = User::authenticated().id(79).name(name, name).build();
+
+            let headers = context.clone().into_headers();
+            let roundtripped = User::try_from(&headers).unwrap();
+            assert_eq!(context, roundtripped, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn from_headers_strict_rejects_duplicates() {
+        let mut headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+        };
+        headers.append("User-ID", "1".parse().unwrap());
+
+        let err = User::from_headers(&headers, &Config::default()).unwrap_err();
+        assert_eq!(err.name.as_str(), "user-id");
+        assert!(matches!(err.kind, ErrorKind::Duplicated));
+
+        let context = User::from_headers(&headers, &Config::new().strict(false)).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.id, UserId::from(55));
+    }
+
+    #[test]
+    fn prefixed_does_not_collide() {
+        let config = Config::new().prefix("X-THA-").unwrap();
+        let context = User::Service(ServiceUser {
+            name: String::from("mailer"),
+            permissions: vec![Permission::CheckIn],
+        });
+
+        let mut headers = headers! {
+            "User-ID" => "vendor-1234",
+        };
+        context
+            .clone()
+            .try_write_headers_with(&mut headers, &config)
+            .unwrap();
+
+        assert_eq!(headers.get("user-id").unwrap(), "vendor-1234");
+        assert_eq!(headers.get("x-tha-user-session").unwrap(), "service");
+        assert_eq!(
+            headers.get("x-tha-service-permissions").unwrap(),
+            "check-in"
+        );
+
+        let roundtripped = User::from_headers(&headers, &config).unwrap();
+        assert_eq!(context, roundtripped);
+    }
+
+    #[test]
+    fn into_headers_tags_protocol_version() {
+        let headers = User::Unauthenticated.into_headers();
+        assert_eq!(
+            headers.get("context-version").unwrap(),
+            PROTOCOL_VERSION.to_string().as_str()
+        );
+    }
+
+    #[test]
+    fn old_writer_new_reader() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "organizer",
+            "User-Is-Admin" => "false",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.role, Some(UserRole::Organizer));
+    }
+
+    #[test]
+    fn new_writer_old_reader_unknown_session() {
+        let headers = headers! {
+            "Context-Version" => (PROTOCOL_VERSION + 1).to_string(),
+            "User-Session" => "passkey-pending",
+            "Passkey-Challenge" => "abc123",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        assert_eq!(context, User::Unauthenticated);
+    }
+
+    #[test]
+    fn new_writer_old_reader_unknown_values() {
+        let headers = headers! {
+            "Context-Version" => (PROTOCOL_VERSION + 1).to_string(),
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "superhero",
+            "User-Is-Admin" => "false",
+            "User-Participant-Status" => "graduated",
+            "User-Memberships" => "wafflehacks-2023=manager,wafflehacks-2024=superhero",
+            "User-Pronouns" => "they/them",
+            "Session-ID" => "abc123",
+            "Session-Created-At" => "1700000000",
+            "Session-Expires-At" => "1700086400",
+            "Session-Provider" => "myspace",
+            "Auth-Assurance-Level" => "passkey",
+            "Auth-Authenticated-At" => "1700000000",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.role, None);
+        assert_eq!(context.participant_status, None);
+        assert_eq!(
+            context.memberships,
+            Some(vec![EventMembership {
+                event: Slug::new("wafflehacks-2023").unwrap(),
+                role: UserRole::Manager,
+            }])
+        );
+        assert_eq!(context.session.unwrap().provider, None);
+        assert_eq!(context.assurance, None);
+    }
+
+    #[test]
+    fn unknown_values_are_kept_when_opted_in() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "55",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Role" => "sponsor",
+            "User-Is-Admin" => "false",
+            "User-Memberships" => "wafflehacks-2023=manager,wafflehacks-2024=judge",
+        };
+
+        let config = Config::new().unknown_values(true);
+        let context = User::from_headers(&headers, &config).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+        assert_eq!(context.role, Some(UserRole::Unknown("sponsor".to_owned())));
+        assert_eq!(
+            context.membership("wafflehacks-2024"),
+            Some(UserRole::Unknown("judge".to_owned()))
+        );
+        assert_eq!(
+            context
+                .events_with_role(UserRole::Participant)
+                .collect::<Vec<_>>(),
+            vec!["wafflehacks-2023"]
+        );
+
+        let err = User::from_headers(&headers, &Config::new()).unwrap_err();
+        assert_eq!(err.name.as_str(), "user-role");
+        assert!(matches!(err.kind, ErrorKind::Error(_)));
+    }
+
+    #[test]
+    fn unknown_session_is_unauthenticated_when_opted_in() {
+        let headers = headers! {
+            "User-Session" => "passkey-pending",
+        };
+
+        let config = Config::new().unknown_values(true);
+        let context = User::from_headers(&headers, &config).unwrap();
+        assert_eq!(context, User::Unauthenticated);
+    }
+
+    #[test]
+    fn malformed_values_are_rejected_when_opted_in() {
+        let headers = headers! {
+            "User-Session" => "Passkey Pending",
+        };
+
+        let config = Config::new().unknown_values(true);
+        let err = User::from_headers(&headers, &config).unwrap_err();
+        assert_eq!(err.name.as_str(), "user-session");
+        assert!(matches!(err.kind, ErrorKind::Error(_)));
+    }
+
+    fn authenticated_with_role(role: UserRole) -> AuthenticatedUser {
+        User::authenticated().id(79).role(role).into()
+    }
+
+    #[test]
+    fn unknown_role_roundtrips() {
+        let mut context = authenticated_with_role(UserRole::Unknown("sponsor".to_owned()));
+        context.memberships = Some(vec![EventMembership {
+            event: Slug::new("wafflehacks-2024").unwrap(),
+            role: UserRole::Unknown("judge".to_owned()),
+        }]);
+        let user = User::Authenticated(context);
+
+        let headers = user.clone().into_headers();
+        assert_eq!(headers.get("user-role").unwrap(), "sponsor");
+
+        let config = Config::new().unknown_values(true);
+        assert_eq!(User::from_headers(&headers, &config).unwrap(), user);
+    }
+
+    #[test]
+    fn unknown_role_must_be_a_token() {
+        let context = authenticated_with_role(UserRole::Unknown("Sponsor, Really".to_owned()));
+
+        let err = User::Authenticated(context).try_into_headers().unwrap_err();
+        assert_eq!(err.name.as_str(), "user-role");
+    }
+
+    #[test]
+    fn unknown_roles_are_never_at_least() {
+        let unknown = UserRole::Unknown("sponsor".to_owned());
+
+        assert!(unknown < UserRole::Participant);
+        assert!(!unknown.is_at_least(&UserRole::Participant));
+        assert!(!unknown.is_at_least(&unknown));
+        assert!(!UserRole::Director.is_at_least(&unknown));
+        assert!(UserRole::Director.is_at_least(&UserRole::Organizer));
+    }
+
+    #[test]
+    fn new_writer_old_reader_unknown_permission() {
+        let headers = headers! {
+            "Context-Version" => (PROTOCOL_VERSION + 1).to_string(),
+            "User-Session" => "service",
+            "Service-Name" => "mailer",
+            "Service-Permissions" => "check-in,launch-rockets",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Service(context) = context else {
+            panic!("expected Context::Service, got {:?}", context);
+        };
+        assert!(context.permissions.is_empty());
+    }
+
+    #[test]
+    fn try_write_headers_is_atomic() {
+        let mut headers = headers! {
+            "User-Session" => "unauthenticated",
+        };
+
+        let context = User::Service(ServiceUser {
+            name: String::from("mäiler"),
+            permissions: vec![Permission::CheckIn],
+        });
+        let err = context.try_write_headers(&mut headers).unwrap_err();
+
+        assert_eq!(err.name.as_str(), "service-name");
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("user-session").unwrap(), "unauthenticated");
+    }
+
+    #[test]
+    #[should_panic(expected = "user must be representable as headers")]
+    fn into_headers_panics_on_unrepresentable_value() {
+        let context = User::RegistrationNeeded(UserRegistrationNeeded {
+            provider: OAuthProvider::Google,
+            id: String::from("0123\n4567"),
+            email: Email::new("hello@world.com").unwrap(),
+        });
+        context.into_headers();
+    }
+
+    #[test]
+    fn try_into_headers_arbitrary_strings() {
+        let is_representable = |value: &str| {
+            value
+                .bytes()
+                .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+        };
+
+        for value in crate::test_util::arbitrary_strings() {
+            let contexts = [
+                (
+                    "oauth-user-id",
+                    User::RegistrationNeeded(UserRegistrationNeeded {
+                        provider: OAuthProvider::GitHub,
+                        id: value.clone(),
+                        email: Email::new("hello@world.com").unwrap(),
+                    }),
+                ),
+                (
+                    "session-id",
+                    User::authenticated()
+                        .id(79)
+                        .name(&value, &value)
+                        .impersonated_by(3, &value, from_unix_seconds(1_700_000_000))
+                        .session(SessionMetadata {
+                            id: value.clone(),
+                            created_at: from_unix_seconds(1_700_000_000),
+                            expires_at: from_unix_seconds(1_700_086_400),
+                            provider: None,
+                        })
+                        .build(),
+                ),
+                (
+                    "service-name",
+                    User::Service(ServiceUser {
+                        name: value.clone(),
+                        permissions: Vec::new(),
+                    }),
+                ),
+            ];
+
+            for (header, context) in contexts {
+                match context.clone().try_into_headers() {
+                    Ok(headers) => {
+                        assert!(is_representable(&value), "{value:?}");
+                        let roundtripped = User::try_from(&headers).unwrap();
+                        assert_eq!(context, roundtripped, "{value:?}");
+                    }
+                    Err(e) => {
+                        assert!(!is_representable(&value), "{value:?}");
+                        assert_eq!(e.name.as_str(), header, "{value:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn into_headers_service() {
+        let context = User::Service(ServiceUser {
+            name: String::from("mailer"),
+            permissions: vec![Permission::ReadApplications, Permission::CheckIn],
+        });
+        let headers = context.into_headers();
+
+        assert_eq!(headers.get("user-session").unwrap(), "service");
+        assert_eq!(headers.get("service-name").unwrap(), "mailer");
+        assert_eq!(
+            headers.get("service-permissions").unwrap(),
+            "read-applications,check-in"
+        );
+    }
+
+    #[test]
+    fn service_serializes_as_tagged_union() {
+        let context = User::Service(ServiceUser {
+            name: String::from("mailer"),
+            permissions: vec![Permission::CheckIn],
+        });
+        let serialized = serde_json::to_string(&context).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"type":"service","name":"mailer","permissions":["check-in"]}"#
+        );
+
+        let deserialized: User = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, context);
+    }
+
+    #[test]
+    fn impersonated_serializes_started_at_as_unix_seconds() {
+        let impersonator = Impersonator {
+            id: UserId::from(3),
+            reason: String::from("debugging"),
+            started_at: from_unix_seconds(1_700_000_000),
+        };
+        let serialized = serde_json::to_string(&impersonator).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"id":3,"reason":"debugging","started_at":1700000000}"#
+        );
+
+        let deserialized: Impersonator = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, impersonator);
+    }
+
+    macro_rules! test_roundtrip {
+        ( $(
+            $name:ident ( $context:expr )
+        );+ $(;)? ) => {
+            $(
                 #[test]
                 fn $name() {
                     let context = $context;
@@ -617,20 +2415,88 @@ mod tests {
         roundtrip_unauthenticated(User::Unauthenticated);
         roundtrip_oauth(User::OAuth);
         roundtrip_registration_needed(User::RegistrationNeeded(UserRegistrationNeeded {
-            provider: String::from("google"),
+            provider: OAuthProvider::Google,
             id: String::from("01234567890"),
-            email: String::from("hellö@wörld.cöm"),
+            email: Email::new("hellö@wörld.cöm").unwrap(),
         }));
-        roundtrip_authenticated(User::Authenticated(AuthenticatedUser {
-            id: 79,
-            given_name: String::from("Jöhn"),
-            family_name: String::from("Döe"),
-            email: String::from("jöhn.döe@gmail.cöm"),
-            role: Some(UserRole::Participant),
-            is_admin: false,
+        roundtrip_authenticated(User::authenticated()
+            .id(79)
+            .name("Jöhn", "Döe")
+            .email("jöhn.döe@gmail.cöm")
+            .role(UserRole::Participant)
+            .participant_status(ParticipantStatus::Accepted)
+            .build());
+        roundtrip_authenticated_impersonated(User::authenticated()
+            .id(79)
+            .role(UserRole::Participant)
+            .impersonated_by(3, "Dëbugging application", from_unix_seconds(1_700_000_000))
+            .build());
+        roundtrip_authenticated_with_session(User::authenticated()
+            .id(79)
+            .admin()
+            .membership("wafflehacks-2023", UserRole::Manager)
+            .session(SessionMetadata {
+                id: String::from("abc123"),
+                created_at: from_unix_seconds(1_700_000_000),
+                expires_at: from_unix_seconds(1_700_086_400),
+                provider: Some(OAuthProvider::Google),
+            })
+            .build());
+        roundtrip_authenticated_with_assurance(User::authenticated()
+            .id(79)
+            .assurance(AssuranceLevel::Password, from_unix_seconds(1_700_000_000))
+            .build());
+        roundtrip_service(User::Service(ServiceUser {
+            name: String::from("mailer"),
+            permissions: vec![Permission::ReadApplications, Permission::ManageEvent],
         }));
     }
 
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn try_from_authenticated_64_bit_id() {
+        let headers = headers! {
+            "User-Session" => "authenticated",
+            "User-ID" => "3000000000",
+            "User-Given-Name" => "John",
+            "User-Family-Name" => "Doe",
+            "User-Email" => "john.doe@gmail.com",
+            "User-Is-Admin" => "false",
+            "Impersonator-ID" => "3000000001",
+            "Impersonation-Reason" => "Debugging",
+            "Impersonation-Started-At" => "1700000000",
+        };
+
+        let context = User::try_from(&headers).unwrap();
+        let User::Authenticated(context) = context else {
+            panic!("expected Context::Authenticated, got {:?}", context);
+        };
+
+        assert_eq!(context.id, UserId::new(3_000_000_000));
+        assert_eq!(context.impersonator.unwrap().id, UserId::new(3_000_000_001));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn roundtrip_authenticated_uuid() {
+        let id = "67e55044-10b1-426f-9247-bb680e5fe0c8"
+            .parse::<uuid::Uuid>()
+            .unwrap();
+        let context = User::authenticated()
+            .id(id)
+            .impersonated_by(7, "Debugging", from_unix_seconds(1_700_000_000))
+            .build();
+
+        let headers = context.clone().into_headers();
+        assert_eq!(
+            headers.get("User-ID").unwrap(),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+
+        let roundtripped = User::try_from(&headers).unwrap();
+        assert_eq!(context, roundtripped);
+    }
+
     #[test]
     fn user_role_ordering() {
         assert!(UserRole::Director > UserRole::Manager);
@@ -639,5 +2505,137 @@ mod tests {
         assert!(UserRole::Manager > UserRole::Organizer);
         assert!(UserRole::Manager > UserRole::Participant);
         assert!(UserRole::Organizer > UserRole::Participant);
+        assert!(UserRole::Owner > UserRole::Director);
+        assert!(UserRole::Organizer > UserRole::Volunteer);
+        assert!(UserRole::Volunteer > UserRole::Participant);
+    }
+
+    #[test]
+    fn role_window() {
+        let mut user = authenticated_with_role(UserRole::Manager);
+        user.role_window = Some(RoleWindow {
+            valid_from: from_unix_seconds(1_700_000_000),
+            valid_until: from_unix_seconds(1_700_172_800),
+        });
+        let event = Scope::Event(EventScope {
+            event: Slug::new("testing").unwrap(),
+            organization_id: OrganizationId::from(5),
+            domain: None,
+        });
+
+        let before = from_unix_seconds(1_699_999_999);
+        assert!(!user.role_active_at(before));
+        assert_eq!(user.role_for_at(&event, before), None);
+
+        let during = from_unix_seconds(1_700_086_400);
+        assert!(user.role_active_at(during));
+        assert_eq!(user.role_for_at(&event, during), Some(UserRole::Manager));
+
+        let after = from_unix_seconds(1_700_172_800);
+        assert!(!user.role_active_at(after));
+        assert_eq!(user.role_for_at(&event, after), None);
+
+        user.role_window = None;
+        assert!(user.role_active_at(after));
+    }
+
+    /// Serialize the user into headers as if it were written at the time
+    fn into_headers_at(user: User, seconds: u64) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let clock = FixedClock::new(from_unix_seconds(seconds));
+        user.try_write_headers_with_clock(&mut headers, &Config::default(), &clock)
+            .unwrap();
+        headers
+    }
+
+    #[test]
+    fn role_window_as_headers() {
+        let mut user = authenticated_with_role(UserRole::Organizer);
+        user.role_window = Some(RoleWindow {
+            valid_from: from_unix_seconds(1_700_000_000),
+            valid_until: from_unix_seconds(1_700_172_800),
+        });
+        let user = User::Authenticated(user);
+
+        let headers = into_headers_at(user.clone(), 1_700_086_400);
+        assert_eq!(headers.get("user-role-valid-from").unwrap(), "1700000000");
+        assert_eq!(headers.get("user-role-valid-until").unwrap(), "1700172800");
+        assert_eq!(User::try_from(&headers).unwrap(), user);
+
+        let serialized = serde_json::to_value(&user).unwrap();
+        assert_eq!(
+            serialized["role_window"],
+            serde_json::json!({ "valid_from": 1_700_000_000, "valid_until": 1_700_172_800 })
+        );
+    }
+
+    #[test]
+    fn inactive_role_is_not_written() {
+        let mut user = authenticated_with_role(UserRole::Organizer);
+        user.role_window = Some(RoleWindow {
+            valid_from: from_unix_seconds(1_700_000_000),
+            valid_until: from_unix_seconds(1_700_172_800),
+        });
+        let user = User::Authenticated(user);
+
+        for seconds in [1_699_999_999, 1_700_172_800] {
+            let headers = into_headers_at(user.clone(), seconds);
+            assert!(headers.get("user-role").is_none());
+            assert!(headers.get("user-role-valid-from").is_none());
+            assert!(headers.get("user-role-valid-until").is_none());
+
+            let User::Authenticated(context) = User::try_from(&headers).unwrap() else {
+                panic!("expected Context::Authenticated");
+            };
+            assert_eq!(context.role, None);
+            assert_eq!(context.role_window, None);
+        }
+    }
+
+    #[test]
+    fn user_role_is_at_least() {
+        assert!(UserRole::Owner.is_at_least(&UserRole::Director));
+        assert!(!UserRole::Director.is_at_least(&UserRole::Owner));
+        assert!(UserRole::Volunteer.is_at_least(&UserRole::Participant));
+        assert!(UserRole::Volunteer.is_at_least(&UserRole::Volunteer));
+        assert!(!UserRole::Volunteer.is_at_least(&UserRole::Organizer));
+    }
+
+    #[test]
+    fn new_roles_as_headers() {
+        for (role, name) in [
+            (UserRole::Owner, "owner"),
+            (UserRole::Volunteer, "volunteer"),
+        ] {
+            let headers = User::Authenticated(authenticated_with_role(role.clone())).into_headers();
+            assert_eq!(headers.get("user-role").unwrap(), name);
+
+            let User::Authenticated(context) = User::try_from(&headers).unwrap() else {
+                panic!("expected Context::Authenticated");
+            };
+            assert_eq!(context.role, Some(role));
+        }
+    }
+
+    #[test]
+    fn new_roles_serde() {
+        let serialized = serde_json::to_string(&UserRole::Volunteer).unwrap();
+        assert_eq!(serialized, r#""volunteer""#);
+
+        let deserialized: UserRole = serde_json::from_str(r#""owner""#).unwrap();
+        assert_eq!(deserialized, UserRole::Owner);
+    }
+
+    #[test]
+    fn role_serde_uses_transmitted_names() {
+        let sponsor = UserRole::Unknown(String::from("sponsor"));
+        let serialized = serde_json::to_string(&sponsor).unwrap();
+        assert_eq!(serialized, r#""sponsor""#);
+        assert_eq!(
+            serde_json::from_str::<UserRole>(&serialized).unwrap(),
+            sponsor
+        );
+
+        assert!(serde_json::from_str::<UserRole>(r#""Owner""#).is_err());
     }
 }
@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Borrow,
+    error::Error,
+    fmt::{Display, Formatter},
+    ops::Deref,
+    str::FromStr,
+};
+
+/// Whether the value could be a member of an enumerated value, i.e. a role
+///
+/// Only lowercase ASCII letters, digits, hyphens, and underscores are allowed, so unknown values
+/// can be safely carried in lists and written back out.
+pub(crate) fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+}
+
+/// The reason a value failed validation
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The value was empty
+    Empty,
+    /// The value was longer than the maximum number of characters
+    TooLong {
+        /// The maximum number of characters allowed
+        max: usize,
+    },
+    /// The value contained a character that is not allowed
+    InvalidCharacter(char),
+    /// The value was not in the expected format
+    InvalidFormat(&'static str),
+    /// The value is not a known OAuth provider
+    UnknownProvider,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("value must not be empty"),
+            Self::TooLong { max } => write!(f, "value must be at most {max} characters"),
+            Self::InvalidCharacter(c) => write!(f, "value must not contain {c:?}"),
+            Self::InvalidFormat(reason) => write!(f, "value is malformed: {reason}"),
+            Self::UnknownProvider => f.write_str("value is not a known OAuth provider"),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+macro_rules! validated_string {
+    (
+        $( #[$attr:meta] )*
+        $name:ident, $validate:ident
+    ) => {
+        $( #[$attr] )*
+        #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+        #[serde(try_from = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            /// Validate and wrap the value
+            pub fn new(value: impl Into<String>) -> Result<Self, ValidationError> {
+                let value = value.into();
+                $validate(&value)?;
+                Ok(Self(value))
+            }
+
+            /// Get the value as a string slice
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Unwrap the value
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = ValidationError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = ValidationError;
+
+            fn try_from(value: &str) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ValidationError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+/// The maximum length of a slug, matching the length of a DNS label
+const SLUG_MAX_LENGTH: usize = 63;
+
+fn validate_slug(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+    if value.len() > SLUG_MAX_LENGTH {
+        return Err(ValidationError::TooLong {
+            max: SLUG_MAX_LENGTH,
+        });
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '-'))
+    {
+        return Err(ValidationError::InvalidCharacter(c));
+    }
+    if value.starts_with('-') || value.ends_with('-') {
+        return Err(ValidationError::InvalidFormat(
+            "must not start or end with a hyphen",
+        ));
+    }
+
+    Ok(())
+}
+
+validated_string! {
+    /// A URL-safe identifier, such as an event slug
+    ///
+    /// Slugs are between 1 and 63 characters made up of lowercase ASCII letters, digits, and
+    /// hyphens, and cannot start or end with a hyphen.
+    Slug, validate_slug
+}
+
+/// The maximum length of an email address
+const EMAIL_MAX_LENGTH: usize = 254;
+/// The maximum length of the local part of an email address
+const EMAIL_LOCAL_MAX_LENGTH: usize = 64;
+
+fn validate_email(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+    if value.chars().count() > EMAIL_MAX_LENGTH {
+        return Err(ValidationError::TooLong {
+            max: EMAIL_MAX_LENGTH,
+        });
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || matches!(c, ',' | '<' | '>'))
+    {
+        return Err(ValidationError::InvalidCharacter(c));
+    }
+
+    let Some((local, domain)) = value.rsplit_once('@') else {
+        return Err(ValidationError::InvalidFormat("must contain an `@`"));
+    };
+    if local.is_empty() || local.contains('@') {
+        return Err(ValidationError::InvalidFormat(
+            "must have exactly one non-empty local part",
+        ));
+    }
+    if local.chars().count() > EMAIL_LOCAL_MAX_LENGTH {
+        return Err(ValidationError::InvalidFormat(
+            "local part must be at most 64 characters",
+        ));
+    }
+    if !domain.contains('.') || domain.split('.').any(str::is_empty) {
+        return Err(ValidationError::InvalidFormat(
+            "domain must contain at least two non-empty labels",
+        ));
+    }
+
+    Ok(())
+}
+
+validated_string! {
+    /// A syntactically valid email address
+    ///
+    /// Only the overall shape of the address is checked, internationalized addresses are allowed.
+    Email, validate_email
+}
+
+/// The OAuth providers users can authenticate with
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuthProvider {
+    /// [Discord](https://discord.com)
+    Discord,
+    /// [GitHub](https://github.com)
+    GitHub,
+    /// [Google](https://google.com)
+    Google,
+    /// [Microsoft](https://microsoft.com)
+    Microsoft,
+}
+
+impl OAuthProvider {
+    /// The slug of the provider as it is transmitted
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Discord => "discord",
+            Self::GitHub => "github",
+            Self::Google => "google",
+            Self::Microsoft => "microsoft",
+        }
+    }
+}
+
+impl FromStr for OAuthProvider {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "discord" => Self::Discord,
+            "github" => Self::GitHub,
+            "google" => Self::Google,
+            "microsoft" => Self::Microsoft,
+            "" => return Err(ValidationError::Empty),
+            _ => return Err(ValidationError::UnknownProvider),
+        })
+    }
+}
+
+impl TryFrom<String> for OAuthProvider {
+    type Error = ValidationError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for OAuthProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "graphql")]
+#[async_graphql::Scalar]
+impl async_graphql::ScalarType for Slug {
+    fn parse(value: async_graphql::Value) -> async_graphql::InputValueResult<Self> {
+        match value {
+            async_graphql::Value::String(value) => {
+                Self::new(value).map_err(async_graphql::InputValueError::custom)
+            }
+            value => Err(async_graphql::InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> async_graphql::Value {
+        async_graphql::Value::String(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Email, OAuthProvider, Slug, ValidationError};
+
+    #[test]
+    fn valid_slugs() {
+        for slug in ["wafflehacks", "wafflehacks-2023", "a", "0x"] {
+            assert!(Slug::new(slug).is_ok(), "{slug}");
+        }
+        assert!(Slug::new("a".repeat(63)).is_ok());
+    }
+
+    #[test]
+    fn invalid_slugs() {
+        assert_eq!(Slug::new(""), Err(ValidationError::Empty));
+        assert_eq!(
+            Slug::new("a".repeat(64)),
+            Err(ValidationError::TooLong { max: 63 })
+        );
+        assert_eq!(
+            Slug::new("WaffleHacks"),
+            Err(ValidationError::InvalidCharacter('W'))
+        );
+        assert_eq!(
+            Slug::new("waffle hacks"),
+            Err(ValidationError::InvalidCharacter(' '))
+        );
+        assert_eq!(
+            Slug::new("wäfflehacks"),
+            Err(ValidationError::InvalidCharacter('ä'))
+        );
+        assert!(matches!(
+            Slug::new("-wafflehacks"),
+            Err(ValidationError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn valid_emails() {
+        for email in [
+            "john.doe@gmail.com",
+            "jöhn.döe@gmail.cöm",
+            "hello+tag@sub.domain.io",
+        ] {
+            assert!(Email::new(email).is_ok(), "{email}");
+        }
+    }
+
+    #[test]
+    fn invalid_emails() {
+        assert_eq!(Email::new(""), Err(ValidationError::Empty));
+        assert_eq!(
+            Email::new("john doe@gmail.com"),
+            Err(ValidationError::InvalidCharacter(' '))
+        );
+        for email in [
+            "john.doe",
+            "@gmail.com",
+            "john@doe@gmail.com",
+            "john.doe@localhost",
+            "john.doe@gmail..com",
+            "john.doe@gmail.com.",
+        ] {
+            assert!(
+                matches!(Email::new(email), Err(ValidationError::InvalidFormat(_))),
+                "{email}"
+            );
+        }
+        assert!(matches!(
+            Email::new(format!("{}@gmail.com", "a".repeat(65))),
+            Err(ValidationError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn providers() {
+        assert_eq!("google".parse(), Ok(OAuthProvider::Google));
+        assert_eq!("github".parse(), Ok(OAuthProvider::GitHub));
+        assert_eq!(
+            "myspace".parse::<OAuthProvider>(),
+            Err(ValidationError::UnknownProvider)
+        );
+        assert_eq!(OAuthProvider::Microsoft.as_str(), "microsoft");
+    }
+
+    #[test]
+    fn deserialize_validates() {
+        let slug: Slug = serde_json::from_str(r#""wafflehacks""#).unwrap();
+        assert_eq!(slug, "wafflehacks");
+        assert!(serde_json::from_str::<Slug>(r#""Waffle Hacks""#).is_err());
+
+        assert!(serde_json::from_str::<Email>(r#""not-an-email""#).is_err());
+
+        let provider: OAuthProvider = serde_json::from_str(r#""github""#).unwrap();
+        assert_eq!(provider, OAuthProvider::GitHub);
+        assert!(serde_json::from_str::<OAuthProvider>(r#""myspace""#).is_err());
+    }
+
+    #[test]
+    fn serialize_as_string() {
+        let slug = Slug::new("wafflehacks").unwrap();
+        assert_eq!(serde_json::to_string(&slug).unwrap(), r#""wafflehacks""#);
+    }
+}